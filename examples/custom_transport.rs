@@ -0,0 +1,77 @@
+//! Drives a TLC5940 through a `Connector` written outside this crate.
+//!
+//! `HexDump` stands in for a transport the crate doesn't know about, e.g.
+//! a PIO state machine or a register-level SPI driver, and just prints
+//! each frame. `CountingConnector` wraps any other connector to show that
+//! the ones in `tlc5940::connectors` can be composed the same way.
+//!
+//! Run with `cargo run --example custom_transport`.
+
+use tlc5940::connectors::Connector;
+use tlc5940::{Result, Unconnected, TLC5940};
+
+/// Prints every frame as hex instead of sending it anywhere
+#[derive(Debug, Default)]
+struct HexDump {
+    pending: Vec<u8>,
+}
+
+impl Connector for HexDump {
+    fn shift(&mut self, data: &[u8]) -> Result<()> {
+        self.pending.clear();
+        self.pending.extend_from_slice(data);
+        Ok(())
+    }
+
+    fn latch(&mut self) -> Result<()> {
+        let hex: Vec<String> =
+            self.pending.iter().map(|b| format!("{:02x}", b)).collect();
+        println!("latched {}", hex.join(" "));
+        Ok(())
+    }
+}
+
+/// Counts the bytes and latches passing through another connector
+#[derive(Debug)]
+struct CountingConnector<INNER> {
+    inner: INNER,
+    bytes: usize,
+    latches: usize,
+}
+
+impl<INNER: Connector> Connector for CountingConnector<INNER> {
+    fn shift(&mut self, data: &[u8]) -> Result<()> {
+        self.bytes += data.len();
+        self.inner.shift(data)
+    }
+
+    fn latch(&mut self) -> Result<()> {
+        self.latches += 1;
+        self.inner.latch()
+    }
+}
+
+fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    let connector = CountingConnector {
+        inner: HexDump::default(),
+        bytes: 0,
+        latches: 0,
+    };
+    let mut tlc: TLC5940<_, _, _, _> = TLC5940::from_connector(
+        connector,
+        Unconnected,
+        Unconnected,
+        Unconnected,
+    )
+    .map_err(|e| format!("failed to set up the TLC5940: {:?}", e))?;
+
+    for output in 0..4 {
+        tlc.set_levels([0; 16])
+            .and_then(|()| tlc.set_level(output, 4095))
+            .and_then(|()| tlc.update())
+            .map_err(|e| format!("failed to update: {:?}", e))?;
+    }
+
+    println!("{:?}", tlc);
+    Ok(())
+}
@@ -0,0 +1,62 @@
+//! Exercises a TLC5940 from a PC through an FT232H, so that a board can
+//! be brought up before any MCU firmware exists.
+//!
+//! Run with `cargo run --example ftdi --features ftdi-host`.
+//!
+//! Wiring (FT232H MPSSE pins):
+//!
+//! * AD0 (SCK) -> SCLK
+//! * AD1 (MOSI) -> SIN
+//! * AD4 -> XLAT
+//! * AD5 -> BLANK
+//! * AD6 -> VPRG
+//! * AD7 <- XERR, with a pullup
+//!
+//! GSCLK has to come from elsewhere, e.g. a function generator.
+//!
+//! Every GPIO change is a USB round trip of around a millisecond, so XLAT
+//! is driven as a GPIO alongside the MPSSE SPI (`from_spi_cs`) rather than
+//! bit-banging the whole frame. A frame then takes a few milliseconds to
+//! send, which is plenty for bring-up.
+
+use std::thread::sleep;
+use std::time::Duration;
+
+use ftdi_embedded_hal::FtHal;
+use tlc5940::TLC5940;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let device = ftdi::find_by_vid_pid(0x0403, 0x6014)
+        .interface(ftdi::Interface::A)
+        .open()?;
+    let hal = FtHal::init_freq(device, 3_000_000)?;
+
+    let mut tlc: TLC5940<_, _, _, _> = TLC5940::from_spi_cs(
+        1,
+        hal.spi()?,
+        hal.ad4()?,
+        hal.ad6()?,
+        hal.ad5()?,
+        hal.adi7()?,
+    )
+    .map_err(|e| format!("failed to set up the TLC5940: {:?}", e))?;
+
+    tlc.set_dot_correction()
+        .map_err(|e| format!("failed to write dot correction: {:?}", e))?;
+    tlc.blank(false)
+        .map_err(|e| format!("failed to unblank: {:?}", e))?;
+
+    // Walk a single lit output along the chip
+    for output in (0..16).cycle() {
+        tlc.set_levels([0; 16])
+            .and_then(|()| tlc.set_level(output, 4095))
+            .and_then(|()| tlc.update())
+            .map_err(|e| format!("failed to update: {:?}", e))?;
+
+        if tlc.error_flag().unwrap_or(false) {
+            println!("XERR asserted with output {} on", output);
+        }
+        sleep(Duration::from_millis(250));
+    }
+    Ok(())
+}
@@ -1,12 +1,23 @@
-use embedded_hal::blocking::spi::Write;
-use embedded_hal::digital::v2::OutputPin;
+use core::fmt;
 
-use crate::{Error, Result};
+use embedded_hal::blocking::i2c;
+use embedded_hal::blocking::spi::{Transfer, Write};
+use embedded_hal::digital::v2::{InputPin, OutputPin};
 
-/// Describes the interface used to connect to the MX7219
+use crate::{Error, Result, Unconnected};
+
+///
+/// Describes the interface used to connect to the chip. Shifting data
+/// in and latching it are separate steps so that the driver controls
+/// when XLAT happens.
+///
+/// Implement this to drive the chips over a transport this crate
+/// doesn't cover, or to wrap one of the connectors here, and pass it to
+/// a driver's `from_connector`. See `examples/custom_transport.rs`.
+///
 pub trait Connector {
     ///
-    /// Writes a byte array to the device
+    /// Shifts a byte array into the device without latching it
     ///
     /// # Arguments
     ///
@@ -16,127 +27,1026 @@ pub trait Connector {
     ///
     /// * `DataError` - returned in case there was an error during data transfer
     ///
-    fn write_raw(&mut self, data: &[u8]) -> Result<()>;
+    fn shift(&mut self, data: &[u8]) -> Result<()>;
+
+    ///
+    /// Pulses XLAT to latch the shifted data into the device
+    ///
+    /// # Errors
+    ///
+    /// * `Error::Xlat` - returned if the XLAT pin could not be driven
+    ///
+    fn latch(&mut self) -> Result<()>;
+
+    ///
+    /// Shifts a byte array into the device and latches it straight away
+    ///
+    fn write_raw(&mut self, data: &[u8]) -> Result<()> {
+        self.shift(data)?;
+        self.latch()
+    }
+
+    ///
+    /// Shifts bytes into the device as they are produced and then latches
+    /// them, so that a long frame never has to be held in RAM in full.
+    ///
+    /// The default collects 32 bytes at a time and hands each chunk to
+    /// `shift`, which assumes that consecutive shifts carry on
+    /// where the last one left off. Connectors for which that isn't true
+    /// return `Error::Unsupported` instead. Being generic, this isn't
+    /// available through `dyn Connector`.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - the bytes to shift in, in the order they go on the wire
+    ///
+    fn write_raw_iter(&mut self, data: impl Iterator<Item = u8>) -> Result<()>
+    where
+        Self: Sized,
+    {
+        let mut buffer = [0_u8; STREAM_CHUNK];
+        let mut len = 0;
+        for byte in data {
+            buffer[len] = byte;
+            len += 1;
+            if len == STREAM_CHUNK {
+                self.shift(&buffer)?;
+                len = 0;
+            }
+        }
+        if len > 0 {
+            self.shift(&buffer[..len])?;
+        }
+        self.latch()
+    }
+
+    ///
+    /// Shifts a byte array into the device without latching it, while
+    /// capturing the same number of bytes from its serial output (SOUT).
+    /// Used to read back the status information data.
+    ///
+    /// # Arguments
+    ///
+    /// * `out` - the data to shift in
+    /// * `input` - filled with the data shifted out of the device
+    ///
+    /// # Errors
+    ///
+    /// * `Error::Unsupported` - the connector can't read from the device
+    /// * `Error::OutOfRange` - `out` and `input` differ in length
+    ///
+    fn write_read(&mut self, out: &[u8], input: &mut [u8]) -> Result<()> {
+        let _ = (out, input);
+        Err(Error::Unsupported)
+    }
+
+    ///
+    /// Asks the connector to latch the shifted data by itself at the end
+    /// of the current grayscale cycle. Returns `false` if it can't, in
+    /// which case the driver waits for `on_blank_interrupt` instead.
+    ///
+    fn latch_at_blank(&mut self) -> Result<bool> {
+        Ok(false)
+    }
+
+    ///
+    /// Whether shifted data stays in the shift register until `latch`
+    /// or `latch_at_blank`. Connectors that latch as part of `shift`,
+    /// such as one with XLAT on the hardware CS line, return `false`,
+    /// and the driver refuses `UpdateStrategy::LatchAtBlank` with them.
+    ///
+    fn can_defer_latch(&self) -> bool {
+        true
+    }
+
+    ///
+    /// Whether XLAT is held low between latches. XLAT is level
+    /// triggered, so while it is high the latch is transparent and the
+    /// shift register is copied into whichever register VPRG selects.
+    /// Connectors with XLAT on an active-low hardware CS, which idles
+    /// high, return `false`, and the driver refuses to switch VPRG with
+    /// them.
+    ///
+    fn latch_idles_low(&self) -> bool {
+        true
+    }
+
+    ///
+    /// Number of chips the connector splits each frame between, if it
+    /// addresses them separately. The driver checks this against its
+    /// channel count when it is constructed.
+    ///
+    fn devices(&self) -> Option<usize> {
+        None
+    }
+
+    ///
+    /// Clocks SCLK once without shifting any data. The chip needs this
+    /// extra pulse after XLAT on the first grayscale frame that follows
+    /// a dot correction write.
+    ///
+    /// Connectors that can't drive SCLK on its own, such as the SPI
+    /// ones, leave it out. With them the first grayscale frame after dot
+    /// correction data may be latched wrongly, so send it twice.
+    ///
+    /// # Errors
+    ///
+    /// * `Error::Pin` - returned if the SCLK pin could not be driven
+    ///
+    fn pulse_sclk(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    ///
+    /// Waits until everything handed to the connector so far has been
+    /// shifted out and latched. The driver calls this before driving
+    /// VPRG, which would otherwise send a frame that is still on its way
+    /// to the wrong register. Connectors that finish each transfer
+    /// before returning have nothing to wait for.
+    ///
+    /// # Errors
+    ///
+    /// * `Error::Spi` - the transport didn't become idle in time
+    ///
+    fn wait_idle(&mut self) -> Result<()> {
+        Ok(())
+    }
 }
 
-/// Direct GPIO pins connector
-pub struct PinConnector<DATA, CS, SCK>
+/// Order in which the bits of each byte are handed to the transport
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum BitOrder {
+    /// Most significant bit first, as the chips expect
+    #[default]
+    MsbFirst,
+    /// Mirror each byte before it is sent. Use this when the SPI
+    /// peripheral is configured to send the least significant bit first
+    /// or the hardware otherwise reverses the bits of each byte.
+    LsbFirst,
+}
+
+impl BitOrder {
+    // Put a byte into the order it should be handed to the transport
+    fn apply(self, byte: u8) -> u8 {
+        match self {
+            BitOrder::MsbFirst => byte,
+            BitOrder::LsbFirst => byte.reverse_bits(),
+        }
+    }
+}
+
+/// Size of the buffer used to mirror bytes before handing them to SPI
+const MIRROR_CHUNK: usize = 32;
+
+/// Number of bytes `Connector::write_raw_iter` collects before each
+/// shift
+const STREAM_CHUNK: usize = 32;
+
+// Write `data` to SPI in the given bit order. Mirrored data is sent in
+// chunks of MIRROR_CHUNK bytes, each in its own `write`.
+fn spi_write<SPI>(spi: &mut SPI, data: &[u8], bit_order: BitOrder) -> Result<()>
+where
+    SPI: Write<u8>,
+{
+    if bit_order == BitOrder::MsbFirst {
+        return spi.write(data).map_err(|_| Error::Spi);
+    }
+
+    let mut buffer = [0_u8; MIRROR_CHUNK];
+    for chunk in data.chunks(MIRROR_CHUNK) {
+        for (out, byte) in buffer.iter_mut().zip(chunk) {
+            *out = bit_order.apply(*byte);
+        }
+        spi.write(&buffer[..chunk.len()]).map_err(|_| Error::Spi)?;
+    }
+    Ok(())
+}
+
+// Drive XLAT high then low again. XLAT is level triggered, so it is
+// left low between latches.
+fn pulse_xlat<XLAT: OutputPin>(xlat: &mut XLAT) -> Result<()> {
+    xlat.set_high().map_err(|_| Error::Xlat)?;
+    xlat.set_low().map_err(|_| Error::Xlat)
+}
+
+/// Direct GPIO pins connector. Reading back from the device needs the
+/// SOUT pin, see `PinConnector::with_sout`.
+pub struct PinConnector<DATA, XLAT, SCK, SOUT = Unconnected>
 where
     DATA: OutputPin,
-    CS: OutputPin,
+    XLAT: OutputPin,
     SCK: OutputPin,
+    SOUT: InputPin,
 {
     data: DATA,
-    cs: CS,
+    xlat: XLAT,
     sck: SCK,
+    /// `None` when built without SOUT, so that `write_read` can refuse
+    /// before shifting anything
+    sout: Option<SOUT>,
+    bit_order: BitOrder,
 }
 
-impl<DATA, CS, SCK> PinConnector<DATA, CS, SCK>
+impl<DATA, XLAT, SCK> PinConnector<DATA, XLAT, SCK>
 where
     DATA: OutputPin,
-    CS: OutputPin,
+    XLAT: OutputPin,
     SCK: OutputPin,
 {
-    pub(crate) fn new(data: DATA, cs: CS, sck: SCK) -> Self {
-        PinConnector { data, cs, sck }
+    /// Bit-bang SIN, XLAT and SCLK on GPIO pins, without reading SOUT
+    pub fn new(data: DATA, xlat: XLAT, sck: SCK) -> Self {
+        PinConnector {
+            data,
+            xlat,
+            sck,
+            sout: None,
+            bit_order: BitOrder::default(),
+        }
     }
 }
 
-impl<DATA, CS, SCK> Connector for PinConnector<DATA, CS, SCK>
+impl<DATA, XLAT, SCK, SOUT> PinConnector<DATA, XLAT, SCK, SOUT>
 where
     DATA: OutputPin,
-    CS: OutputPin,
+    XLAT: OutputPin,
     SCK: OutputPin,
+    SOUT: InputPin,
 {
-    fn write_raw(&mut self, data: &[u8]) -> Result<()> {
-        self.cs.set_low().map_err(|_| Error::Pin)?;
+    /// Bit-bang SIN, XLAT and SCLK on GPIO pins, reading SOUT back on
+    /// another
+    pub fn with_sout(data: DATA, xlat: XLAT, sck: SCK, sout: SOUT) -> Self {
+        PinConnector {
+            data,
+            xlat,
+            sck,
+            sout: Some(sout),
+            bit_order: BitOrder::default(),
+        }
+    }
+
+    /// Set the order the bits of each byte are shifted out in
+    pub fn set_bit_order(&mut self, bit_order: BitOrder) {
+        self.bit_order = bit_order;
+    }
+
+    // Shift one byte out, returning the byte read from SOUT if `read`
+    // is set
+    fn shift_byte(&mut self, value: u8, read: bool) -> Result<u8> {
+        let value = self.bit_order.apply(value);
+        let mut read_value = 0;
+        // Iterate over bits in byte
+        for i in 0..8 {
+            if value & (1 << (7 - i)) > 0 {
+                self.data.set_high().map_err(|_| Error::Pin)?;
+            } else {
+                self.data.set_low().map_err(|_| Error::Pin)?;
+            }
+
+            // SOUT changes on the rising edge of SCLK, so sample it
+            // before clocking
+            let high = match &self.sout {
+                Some(sout) if read => sout.is_high().map_err(|_| Error::Pin)?,
+                _ => false,
+            };
+            if high {
+                read_value |= 1 << (7 - i);
+            }
+
+            self.sck.set_high().map_err(|_| Error::Pin)?;
+            self.sck.set_low().map_err(|_| Error::Pin)?;
+        }
+        Ok(self.bit_order.apply(read_value))
+    }
+}
+
+impl<DATA, XLAT, SCK, SOUT> Connector for PinConnector<DATA, XLAT, SCK, SOUT>
+where
+    DATA: OutputPin,
+    XLAT: OutputPin,
+    SCK: OutputPin,
+    SOUT: InputPin,
+{
+    fn shift(&mut self, data: &[u8]) -> Result<()> {
         // Iterate over byte array
         for value in data {
-            // Iterate over bits in byte
-            for i in 0..8 {
-                if value & (1 << (7 - i)) > 0 {
-                    self.data.set_high().map_err(|_| Error::Pin)?;
-                } else {
-                    self.data.set_low().map_err(|_| Error::Pin)?;
-                }
-
-                self.sck.set_high().map_err(|_| Error::Pin)?;
-                self.sck.set_low().map_err(|_| Error::Pin)?;
-            }
+            self.shift_byte(*value, false)?;
+        }
+
+        Ok(())
+    }
+
+    fn latch(&mut self) -> Result<()> {
+        pulse_xlat(&mut self.xlat)
+    }
+
+    fn write_raw_iter(&mut self, data: impl Iterator<Item = u8>) -> Result<()> {
+        // Bit-banging needs no buffer at all
+        for value in data {
+            self.shift_byte(value, false)?;
         }
-        self.cs.set_high().map_err(|_| Error::Pin)?;
+        self.latch()
+    }
 
+    fn pulse_sclk(&mut self) -> Result<()> {
+        self.sck.set_high().map_err(|_| Error::Pin)?;
+        self.sck.set_low().map_err(|_| Error::Pin)
+    }
+
+    fn write_read(&mut self, out: &[u8], input: &mut [u8]) -> Result<()> {
+        if self.sout.is_none() {
+            return Err(Error::Unsupported);
+        }
+        if out.len() != input.len() {
+            return Err(Error::OutOfRange);
+        }
+        for (value, read) in out.iter().zip(input.iter_mut()) {
+            *read = self.shift_byte(*value, true)?;
+        }
         Ok(())
     }
 }
 
+///
+/// Hardware controlled CS connector with SPI transfer. The CS line is
+/// wired to XLAT, so the data is latched as soon as each transfer ends
+/// and `latch` has nothing left to do. Use `SpiConnectorSW` when the
+/// driver needs to control latch timing.
+///
+/// CS idles high, which leaves the TLC594x's XLAT transparent between
+/// transfers, so dot correction can't be written through this
+/// connector without it ending up in the grayscale register as well.
+///
 pub struct SpiConnector<SPI>
 where
     SPI: Write<u8>,
 {
+    // Only reported in Debug output until chaining uses it
     devices: usize,
-    buffer: [u8; 2],
     spi: SPI,
+    bit_order: BitOrder,
 }
 
-/// Hardware controlled CS connector with SPI transfer
 impl<SPI> SpiConnector<SPI>
 where
     SPI: Write<u8>,
 {
-    pub(crate) fn new(displays: usize, spi: SPI) -> Self {
+    /// SPI with the hardware CS line wired to XLAT
+    ///
+    /// * `displays` - number of displays connected in series
+    pub fn new(displays: usize, spi: SPI) -> Self {
         SpiConnector {
             devices: displays,
-            buffer: [0; 2],
             spi,
+            bit_order: BitOrder::default(),
         }
     }
+
+    /// Set the order the bits of each byte are handed to SPI in. With
+    /// `BitOrder::LsbFirst` frames are mirrored 32 bytes at a time, and
+    /// CS would latch each piece on its own, so `shift` refuses longer
+    /// frames.
+    pub fn set_bit_order(&mut self, bit_order: BitOrder) {
+        self.bit_order = bit_order;
+    }
 }
 
 impl<SPI> Connector for SpiConnector<SPI>
 where
     SPI: Write<u8>,
 {
-    fn write_raw(&mut self, data: &[u8]) -> Result<()> {
-        self.spi.write(data).map_err(|_| Error::Spi)?;
+    fn shift(&mut self, data: &[u8]) -> Result<()> {
+        // Every write ends with a CS edge, which would latch part of the
+        // frame
+        if self.bit_order == BitOrder::LsbFirst && data.len() > MIRROR_CHUNK {
+            return Err(Error::Unsupported);
+        }
+        spi_write(&mut self.spi, data, self.bit_order)
+    }
 
+    fn latch(&mut self) -> Result<()> {
+        // Latched by the hardware CS at the end of the transfer
         Ok(())
     }
+
+    fn can_defer_latch(&self) -> bool {
+        false
+    }
+
+    fn latch_idles_low(&self) -> bool {
+        false
+    }
+
+    fn write_raw_iter(&mut self, data: impl Iterator<Item = u8>) -> Result<()> {
+        // CS would latch each chunk as it ended
+        let _ = data;
+        Err(Error::Unsupported)
+    }
 }
 
-/// Software controlled CS connector with SPI transfer
-pub struct SpiConnectorSW<SPI, CS>
+/// SPI transfer with XLAT driven from a GPIO
+pub struct SpiConnectorSW<SPI, XLAT>
 where
     SPI: Write<u8>,
-    CS: OutputPin,
+    XLAT: OutputPin,
 {
     spi_c: SpiConnector<SPI>,
-    cs: CS,
+    xlat: XLAT,
 }
 
-impl<SPI, CS> SpiConnectorSW<SPI, CS>
+impl<SPI, XLAT> SpiConnectorSW<SPI, XLAT>
 where
     SPI: Write<u8>,
-    CS: OutputPin,
+    XLAT: OutputPin,
 {
-    pub(crate) fn new(displays: usize, spi: SPI, cs: CS) -> Self {
+    /// SPI with XLAT on a GPIO
+    ///
+    /// * `displays` - number of displays connected in series
+    pub fn new(displays: usize, spi: SPI, xlat: XLAT) -> Self {
         SpiConnectorSW {
             spi_c: SpiConnector::new(displays, spi),
-            cs,
+            xlat,
         }
     }
+
+    /// Set the order the bits of each byte are handed to SPI in
+    pub fn set_bit_order(&mut self, bit_order: BitOrder) {
+        self.spi_c.set_bit_order(bit_order);
+    }
 }
 
-impl<SPI, CS> Connector for SpiConnectorSW<SPI, CS>
+impl<SPI, XLAT> Connector for SpiConnectorSW<SPI, XLAT>
 where
     SPI: Write<u8>,
-    CS: OutputPin,
+    XLAT: OutputPin,
 {
-    fn write_raw(&mut self, data: &[u8]) -> Result<()> {
-        self.cs.set_low().map_err(|_| Error::Pin)?;
-        self.spi_c.write_raw(data).map_err(|_| Error::Spi)?;
-        self.cs.set_high().map_err(|_| Error::Pin)?;
+    fn shift(&mut self, data: &[u8]) -> Result<()> {
+        // XLAT is separate from CS, so the frame can go in any number of
+        // writes
+        spi_write(&mut self.spi_c.spi, data, self.spi_c.bit_order)
+    }
+
+    fn latch(&mut self) -> Result<()> {
+        pulse_xlat(&mut self.xlat)
+    }
+}
+
+/// SPI transfer with XLAT driven from a GPIO, which also reads back from
+/// the device's SOUT through MISO
+pub struct SpiTransferConnector<SPI, XLAT>
+where
+    SPI: Write<u8> + Transfer<u8>,
+    XLAT: OutputPin,
+{
+    spi_c: SpiConnectorSW<SPI, XLAT>,
+}
+
+impl<SPI, XLAT> SpiTransferConnector<SPI, XLAT>
+where
+    SPI: Write<u8> + Transfer<u8>,
+    XLAT: OutputPin,
+{
+    /// SPI with XLAT on a GPIO and MISO wired to SOUT
+    pub fn new(spi: SPI, xlat: XLAT) -> Self {
+        SpiTransferConnector {
+            spi_c: SpiConnectorSW::new(1, spi, xlat),
+        }
+    }
+
+    /// Set the order the bits of each byte are handed to SPI in
+    pub fn set_bit_order(&mut self, bit_order: BitOrder) {
+        self.spi_c.set_bit_order(bit_order);
+    }
+}
+
+impl<SPI, XLAT> Connector for SpiTransferConnector<SPI, XLAT>
+where
+    SPI: Write<u8> + Transfer<u8>,
+    XLAT: OutputPin,
+{
+    fn shift(&mut self, data: &[u8]) -> Result<()> {
+        self.spi_c.shift(data)
+    }
+
+    fn latch(&mut self) -> Result<()> {
+        self.spi_c.latch()
+    }
+
+    fn write_read(&mut self, out: &[u8], input: &mut [u8]) -> Result<()> {
+        if out.len() != input.len() {
+            return Err(Error::OutOfRange);
+        }
+        let bit_order = self.spi_c.spi_c.bit_order;
+        for (read, value) in input.iter_mut().zip(out) {
+            *read = bit_order.apply(*value);
+        }
+        self.spi_c
+            .spi_c
+            .spi
+            .transfer(input)
+            .map_err(|_| Error::Spi)?;
+        for read in input.iter_mut() {
+            *read = bit_order.apply(*read);
+        }
+        Ok(())
+    }
+}
+
+/// Most data bytes the SC18IS602 buffers per transfer. The SC18IS606
+/// buffers more, but works just as well with chunks this size.
+const BRIDGE_CHUNK: usize = 200;
+/// Function ID that sends the following bytes out on SPI, asserting
+/// SS0 for the duration
+const BRIDGE_WRITE_SS0: u8 = 0x01;
+/// Function ID that configures the bridge's SPI interface
+const BRIDGE_CONFIGURE: u8 = 0xf0;
+/// SPI configuration: MSB first, mode 0, 1.8MHz
+const BRIDGE_SPI_CONFIG: u8 = 0x00;
+/// Function ID that clears the bridge's interrupt. It has no other
+/// effect, so it is also used to poll the bridge
+const BRIDGE_CLEAR_INTERRUPT: u8 = 0xf1;
+/// Most times the bridge is polled before giving up. A full chunk takes
+/// about 0.9ms to clock out at 1.8MHz, and every poll takes at least
+/// 50us even on a 400kHz bus.
+const BRIDGE_POLLS: usize = 1000;
+
+///
+/// SPI through an SC18IS602 or SC18IS606 I2C-to-SPI bridge, for hosts
+/// that only expose I2C. SIN and SCLK go to the bridge's MOSI and SCLK,
+/// and XLAT is driven from a GPIO. Frames are split into chunks that
+/// fit the bridge's data buffer; the chips don't mind SS being toggled
+/// in between.
+///
+/// The bridge doesn't acknowledge its address while it is clocking a
+/// chunk out on SPI, so it is polled until it does before the next
+/// chunk is sent and before XLAT is pulsed.
+///
+pub struct Sc18is602Connector<I2C, XLAT>
+where
+    I2C: i2c::Write,
+    XLAT: OutputPin,
+{
+    i2c: I2C,
+    /// 7-bit I2C address of the bridge, 0x28-0x2f depending on A0-A2
+    address: u8,
+    xlat: XLAT,
+    bit_order: BitOrder,
+    /// Whether the bridge may still be clocking out the last chunk
+    busy: bool,
+}
+
+impl<I2C, XLAT> Sc18is602Connector<I2C, XLAT>
+where
+    I2C: i2c::Write,
+    XLAT: OutputPin,
+{
+    /// A bridge at the 7-bit `address` with XLAT on a GPIO. Call
+    /// `configure` before using it.
+    pub fn new(i2c: I2C, address: u8, xlat: XLAT) -> Self {
+        Sc18is602Connector {
+            i2c,
+            address,
+            xlat,
+            bit_order: BitOrder::MsbFirst,
+            busy: false,
+        }
+    }
+
+    /// Set up the bridge's SPI interface the way the chips expect
+    pub fn configure(&mut self) -> Result<()> {
+        self.i2c
+            .write(self.address, &[BRIDGE_CONFIGURE, BRIDGE_SPI_CONFIG])
+            .map_err(|_| Error::Spi)
+    }
 
+    /// Set the order the bits of each byte are handed to the bridge in
+    pub fn set_bit_order(&mut self, bit_order: BitOrder) {
+        self.bit_order = bit_order;
+    }
+}
+
+impl<I2C, XLAT> Connector for Sc18is602Connector<I2C, XLAT>
+where
+    I2C: i2c::Write,
+    XLAT: OutputPin,
+{
+    fn shift(&mut self, data: &[u8]) -> Result<()> {
+        let mut buffer = [0_u8; BRIDGE_CHUNK + 1];
+        buffer[0] = BRIDGE_WRITE_SS0;
+        for chunk in data.chunks(BRIDGE_CHUNK) {
+            for (out, byte) in buffer[1..].iter_mut().zip(chunk) {
+                *out = self.bit_order.apply(*byte);
+            }
+            self.wait_idle()?;
+            self.i2c
+                .write(self.address, &buffer[..chunk.len() + 1])
+                .map_err(|_| Error::Spi)?;
+            self.busy = true;
+        }
         Ok(())
     }
+
+    fn latch(&mut self) -> Result<()> {
+        // Latching while the last chunk is still going out would take a
+        // partial frame
+        self.wait_idle()?;
+        pulse_xlat(&mut self.xlat)
+    }
+
+    fn wait_idle(&mut self) -> Result<()> {
+        // The bridge NACKs its address until it has finished clocking
+        // out the last chunk
+        if !self.busy {
+            return Ok(());
+        }
+        for _ in 0..BRIDGE_POLLS {
+            if self
+                .i2c
+                .write(self.address, &[BRIDGE_CLEAR_INTERRUPT])
+                .is_ok()
+            {
+                self.busy = false;
+                return Ok(());
+            }
+        }
+        Err(Error::Spi)
+    }
+}
+
+///
+/// Several chips sharing SIN and SCLK, each with its own XLAT. Every
+/// chip sees the same data in its shift register, but only the one
+/// whose XLAT is pulsed takes it, so the chips can be updated one at a
+/// time.
+///
+/// Frames are laid out as for a chain of `DEVICES` chips. `shift`
+/// splits them up and latches each part into its chip straight away,
+/// so `latch` has nothing left to do. The latches can't be put off,
+/// since each chip's data is gone from the shared lines once the next
+/// chip's has been shifted, so `UpdateStrategy::LatchAtBlank` isn't
+/// supported.
+///
+pub struct ParallelLatchConnector<SHIFT, XLAT, const DEVICES: usize>
+where
+    SHIFT: Connector,
+    XLAT: OutputPin,
+{
+    /// Shifts data onto the shared lines. Its own latch isn't used.
+    shifter: SHIFT,
+    xlats: [XLAT; DEVICES],
+}
+
+impl<SHIFT, XLAT, const DEVICES: usize>
+    ParallelLatchConnector<SHIFT, XLAT, DEVICES>
+where
+    SHIFT: Connector,
+    XLAT: OutputPin,
+{
+    /// Shift through `shifter` and latch each chip with its own XLAT
+    pub fn new(shifter: SHIFT, xlats: [XLAT; DEVICES]) -> Self {
+        ParallelLatchConnector { shifter, xlats }
+    }
+
+    /// Mutable access to the connector used for shifting, e.g. to
+    /// change its bit order
+    pub fn shifter_mut(&mut self) -> &mut SHIFT {
+        &mut self.shifter
+    }
+
+    ///
+    /// Shifts a frame for a single chip and latches it into that chip
+    /// only
+    ///
+    /// # Errors
+    ///
+    /// * `Error::OutOfRange` - there is no chip `device`
+    /// * `Error::Xlat` - the chip's XLAT pin could not be driven
+    ///
+    pub fn shift_device(&mut self, device: usize, data: &[u8]) -> Result<()> {
+        let xlat = self.xlats.get_mut(device).ok_or(Error::OutOfRange)?;
+        self.shifter.shift(data)?;
+        pulse_xlat(xlat)
+    }
+}
+
+impl<SHIFT, XLAT, const DEVICES: usize> Connector
+    for ParallelLatchConnector<SHIFT, XLAT, DEVICES>
+where
+    SHIFT: Connector,
+    XLAT: OutputPin,
+{
+    fn shift(&mut self, data: &[u8]) -> Result<()> {
+        if DEVICES == 0 || data.len() % DEVICES != 0 {
+            return Err(Error::OutOfRange);
+        }
+        // The chip furthest down a chain comes first in the frame
+        let len = data.len() / DEVICES;
+        for (idx, chunk) in data.chunks(len).enumerate() {
+            self.shift_device(DEVICES - 1 - idx, chunk)?;
+        }
+        Ok(())
+    }
+
+    fn latch(&mut self) -> Result<()> {
+        // Each chip was latched as its part of the frame was shifted
+        Ok(())
+    }
+
+    fn write_raw_iter(&mut self, data: impl Iterator<Item = u8>) -> Result<()> {
+        // The frame can only be split between the chips once its length
+        // is known
+        let _ = data;
+        Err(Error::Unsupported)
+    }
+
+    fn can_defer_latch(&self) -> bool {
+        false
+    }
+
+    fn devices(&self) -> Option<usize> {
+        Some(DEVICES)
+    }
+
+    fn pulse_sclk(&mut self) -> Result<()> {
+        self.shifter.pulse_sclk()
+    }
+
+    fn wait_idle(&mut self) -> Result<()> {
+        self.shifter.wait_idle()
+    }
+}
+
+impl<DATA, XLAT, SCK, SOUT> fmt::Debug for PinConnector<DATA, XLAT, SCK, SOUT>
+where
+    DATA: OutputPin,
+    XLAT: OutputPin,
+    SCK: OutputPin,
+    SOUT: InputPin,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PinConnector")
+            .field("bit_order", &self.bit_order)
+            .finish()
+    }
+}
+
+impl<SPI> fmt::Debug for SpiConnector<SPI>
+where
+    SPI: Write<u8>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SpiConnector")
+            .field("devices", &self.devices)
+            .field("bit_order", &self.bit_order)
+            .finish()
+    }
+}
+
+impl<SPI, XLAT> fmt::Debug for SpiConnectorSW<SPI, XLAT>
+where
+    SPI: Write<u8>,
+    XLAT: OutputPin,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SpiConnectorSW")
+            .field("bit_order", &self.spi_c.bit_order)
+            .finish()
+    }
+}
+
+impl<SPI, XLAT> fmt::Debug for SpiTransferConnector<SPI, XLAT>
+where
+    SPI: Write<u8> + Transfer<u8>,
+    XLAT: OutputPin,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SpiTransferConnector")
+            .field("bit_order", &self.spi_c.spi_c.bit_order)
+            .finish()
+    }
+}
+
+impl<SHIFT, XLAT, const DEVICES: usize> fmt::Debug
+    for ParallelLatchConnector<SHIFT, XLAT, DEVICES>
+where
+    SHIFT: Connector + fmt::Debug,
+    XLAT: OutputPin,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ParallelLatchConnector")
+            .field("shifter", &self.shifter)
+            .field("devices", &DEVICES)
+            .finish()
+    }
+}
+
+impl<I2C, XLAT> fmt::Debug for Sc18is602Connector<I2C, XLAT>
+where
+    I2C: i2c::Write,
+    XLAT: OutputPin,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Sc18is602Connector")
+            .field("address", &self.address)
+            .field("bit_order", &self.bit_order)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::*;
+
+    #[test]
+    fn separate_latch() {
+        let mut conn =
+            SpiConnectorSW::new(1, MockSpi::default(), MockPin::default());
+        conn.shift(&[0xaa]).unwrap();
+        assert_eq!(conn.spi_c.spi.len, 1);
+        assert_eq!(conn.xlat.pulses, 0);
+
+        conn.latch().unwrap();
+        assert_eq!(conn.xlat.pulses, 1);
+        assert!(!conn.xlat.high);
+    }
+
+    #[test]
+    fn extra_sclk() {
+        let mut conn = PinConnector::new(
+            MockPin::default(),
+            MockPin::default(),
+            MockPin::default(),
+        );
+        conn.pulse_sclk().unwrap();
+        assert_eq!(conn.sck.pulses, 1);
+        assert!(!conn.sck.high);
+        assert_eq!(conn.data.pulses, 0);
+        assert_eq!(conn.xlat.pulses, 0);
+    }
+
+    #[test]
+    fn write_read() {
+        let mut conn = SpiConnector::new(1, MockSpi::default());
+        let mut input = [0; 2];
+        assert!(matches!(
+            conn.write_read(&[1, 2], &mut input),
+            Err(Error::Unsupported)
+        ));
+
+        // MockSpi echoes back the complement of what was sent
+        let mut conn =
+            SpiTransferConnector::new(MockSpi::default(), MockPin::default());
+        conn.write_read(&[0x0f, 0x80], &mut input).unwrap();
+        assert_eq!(input, [0xf0, 0x7f]);
+        assert!(matches!(
+            conn.write_read(&[1], &mut input),
+            Err(Error::OutOfRange)
+        ));
+
+        conn.set_bit_order(BitOrder::LsbFirst);
+        conn.write_read(&[0x0f, 0x80], &mut input).unwrap();
+        assert_eq!(input, [0xf0, 0x7f]);
+        assert_eq!(&conn.spi_c.spi_c.spi.written[2..4], &[0xf0, 0x01]);
+
+        // Without a SOUT pin the bit-banged connector can't read
+        let mut conn = PinConnector::new(
+            MockPin::default(),
+            MockPin::default(),
+            MockPin::default(),
+        );
+        assert!(matches!(
+            conn.write_read(&[1, 2], &mut input),
+            Err(Error::Unsupported)
+        ));
+        assert_eq!(conn.data.pulses, 0);
+        assert_eq!(conn.sck.pulses, 0);
+    }
+
+    #[test]
+    fn bit_order() {
+        let mut conn = SpiConnector::new(1, MockSpi::default());
+        let data = [0x01, 0x80, 0xf0];
+        conn.shift(&data).unwrap();
+        assert_eq!(&conn.spi.written[..3], &data);
+
+        conn.set_bit_order(BitOrder::LsbFirst);
+        conn.shift(&data).unwrap();
+        assert_eq!(&conn.spi.written[3..6], &[0x80, 0x01, 0x0f]);
+
+        // Mirrored frames longer than one chunk would be latched piece
+        // by piece on hardware CS
+        assert!(matches!(conn.shift(&[0; 33]), Err(Error::Unsupported)));
+        assert_eq!(conn.spi.len, 6);
+        conn.shift(&[0x01; 32]).unwrap();
+        assert_eq!(conn.spi.len, 38);
+
+        let mut conn =
+            SpiConnectorSW::new(1, MockSpi::default(), MockPin::default());
+        conn.set_bit_order(BitOrder::LsbFirst);
+        conn.shift(&[0x01; 72]).unwrap();
+        assert_eq!(conn.spi_c.spi.len, 72);
+        assert_eq!(conn.spi_c.spi.written[71], 0x80);
+    }
+
+    #[test]
+    fn i2c_bridge() {
+        let mut conn = Sc18is602Connector::new(
+            MockI2c::default(),
+            0x28,
+            MockPin::default(),
+        );
+        conn.configure().unwrap();
+        assert_eq!(conn.i2c.writes, 1);
+        assert_eq!(&conn.i2c.written[..2], &[0xf0, 0x00]);
+
+        // 250 bytes go as a full 200 byte chunk and then the remaining
+        // 50, once a poll shows the first has been clocked out
+        let data = [0x5a; 250];
+        conn.shift(&data).unwrap();
+        assert_eq!(conn.i2c.address, 0x28);
+        assert_eq!(conn.i2c.writes, 4);
+        assert_eq!(conn.i2c.len, 2 + 201 + 1 + 51);
+        assert_eq!(conn.i2c.written[2], 0x01);
+        assert_eq!(conn.i2c.written[2 + 201], 0xf1);
+        assert_eq!(conn.i2c.written[2 + 202], 0x01);
+        assert_eq!(conn.xlat.pulses, 0);
+
+        conn.latch().unwrap();
+        assert_eq!(conn.i2c.writes, 5);
+        assert_eq!(conn.xlat.pulses, 1);
+    }
+
+    #[test]
+    fn i2c_bridge_busy() {
+        let mut i2c = MockI2c::default();
+        i2c.busy_polls = 3;
+        let mut conn = Sc18is602Connector::new(i2c, 0x28, MockPin::default());
+
+        // The second chunk waits out the NACKs from the first
+        conn.shift(&[0x5a; 250]).unwrap();
+        assert_eq!(conn.i2c.nacks, 3);
+        assert_eq!(conn.i2c.written[202], 0x01);
+
+        // XLAT waits for the last chunk as well
+        conn.latch().unwrap();
+        assert_eq!(conn.i2c.nacks, 6);
+        assert_eq!(conn.xlat.pulses, 1);
+
+        // A bridge that never finishes is given up on without latching
+        conn.i2c.busy_polls = BRIDGE_POLLS + 1;
+        conn.shift(&[0x5a; 10]).unwrap();
+        assert!(matches!(conn.latch(), Err(Error::Spi)));
+        assert_eq!(conn.xlat.pulses, 1);
+    }
+
+    #[test]
+    fn object_safe() {
+        let mut mock = MockConnector::default();
+        let conn: &mut dyn Connector = &mut mock;
+        conn.write_raw(&[1, 2]).unwrap();
+        assert_eq!(mock.latches, 1);
+    }
+
+    #[test]
+    fn write_raw_iter() {
+        // The default shifts in chunks and latches once at the end
+        let mut conn =
+            SpiConnectorSW::new(1, MockSpi::default(), MockPin::default());
+        conn.write_raw_iter(0..40).unwrap();
+        let spi = &conn.spi_c.spi;
+        assert_eq!(spi.len, 40);
+        assert!(spi.written[..40].iter().copied().eq(0..40));
+        assert_eq!(conn.xlat.pulses, 1);
+
+        // Hardware CS would latch every chunk
+        let mut conn = SpiConnector::new(1, MockSpi::default());
+        assert!(matches!(
+            conn.write_raw_iter(0..40),
+            Err(Error::Unsupported)
+        ));
+        assert_eq!(conn.spi.len, 0);
+    }
+
+    #[test]
+    fn parallel_latch() {
+        let mut conn = ParallelLatchConnector::new(
+            MockConnector::default(),
+            [MockPin::default(), MockPin::default()],
+        );
+        assert!(matches!(conn.shift(&[1, 2, 3]), Err(Error::OutOfRange)));
+
+        // Device 1's half comes first, device 0's is shifted last
+        conn.shift(&[1, 2, 3, 4]).unwrap();
+        assert_eq!(&conn.shifter.last[..conn.shifter.len], &[3, 4]);
+        assert_eq!(conn.xlats[0].pulses, 1);
+        assert_eq!(conn.xlats[1].pulses, 1);
+        assert_eq!(conn.shifter.latches, 0);
+
+        conn.shift_device(1, &[5, 6]).unwrap();
+        assert_eq!(conn.xlats[0].pulses, 1);
+        assert_eq!(conn.xlats[1].pulses, 2);
+        assert!(conn.shift_device(2, &[5, 6]).is_err());
+    }
 }
@@ -0,0 +1,13 @@
+use core::fmt;
+
+/// Summarises a set of levels for `Debug` output rather than printing
+/// every channel
+pub(crate) struct LevelSummary<'a>(pub(crate) &'a [u16]);
+
+impl fmt::Debug for LevelSummary<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let on = self.0.iter().filter(|level| **level > 0).count();
+        let max = self.0.iter().copied().max().unwrap_or(0);
+        write!(f, "{} of {} on, max {}", on, self.0.len(), max)
+    }
+}
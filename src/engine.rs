@@ -0,0 +1,187 @@
+use core::fmt;
+
+use crate::connectors::Connector;
+use crate::{Error, Result};
+
+///
+/// Hardware that takes over shifting frames out, such as an RP2040 PIO
+/// program that also generates GSCLK and BLANK by itself. The driver
+/// still packs the frames, tracks the state of the chip and decides
+/// when they should be latched; the engine only has to move the bits.
+///
+/// Use with `EngineConnector`, and pass `Unconnected` for BLANK if the
+/// engine generates it.
+///
+pub trait ShiftEngine {
+    ///
+    /// Starts shifting a packed frame out. The engine may return before
+    /// the frame has been sent, but has to copy `frame` if it does.
+    ///
+    fn shift_frame(&mut self, frame: &[u8]) -> Result<()>;
+
+    ///
+    /// Latches the last frame as soon as it has been shifted out
+    ///
+    fn latch_now(&mut self) -> Result<()>;
+
+    ///
+    /// Latches the last frame at the end of the current grayscale
+    /// cycle, once it has been shifted out. Used with
+    /// `UpdateStrategy::LatchAtBlank`.
+    ///
+    fn latch_at_boundary(&mut self) -> Result<()>;
+
+    /// Whether a frame is still being shifted out. `EngineConnector`
+    /// waits for this to clear before handing over the next frame.
+    fn busy(&self) -> bool {
+        false
+    }
+
+    /// Whether a latch asked for with `latch_at_boundary` is still
+    /// waiting for the end of the grayscale cycle
+    fn latch_pending(&self) -> bool {
+        false
+    }
+}
+
+/// Most times `EngineConnector` checks the engine before deciding that
+/// it is stuck. A latch at the boundary can take a whole grayscale
+/// cycle, so this is generous.
+const ENGINE_POLLS: u32 = 1_000_000;
+
+/// Adapts a `ShiftEngine` to the `Connector` interface used by the
+/// drivers
+pub struct EngineConnector<ENGINE>
+where
+    ENGINE: ShiftEngine,
+{
+    engine: ENGINE,
+}
+
+impl<ENGINE> EngineConnector<ENGINE>
+where
+    ENGINE: ShiftEngine,
+{
+    /// Hand frames to `engine`
+    pub fn new(engine: ENGINE) -> Self {
+        EngineConnector { engine }
+    }
+
+    /// Access to the engine, e.g. to check whether it is busy
+    pub fn engine(&self) -> &ENGINE {
+        &self.engine
+    }
+
+    /// Mutable access to the engine
+    pub fn engine_mut(&mut self) -> &mut ENGINE {
+        &mut self.engine
+    }
+
+    // Spin while `working` says the engine still has something to do,
+    // giving up after ENGINE_POLLS checks
+    fn wait_for(&self, working: impl Fn(&ENGINE) -> bool) -> Result<()> {
+        for _ in 0..ENGINE_POLLS {
+            if !working(&self.engine) {
+                return Ok(());
+            }
+            core::hint::spin_loop();
+        }
+        Err(Error::Spi)
+    }
+}
+
+impl<ENGINE> Connector for EngineConnector<ENGINE>
+where
+    ENGINE: ShiftEngine,
+{
+    fn shift(&mut self, data: &[u8]) -> Result<()> {
+        // A new frame would cut into the one still going out
+        self.wait_for(ENGINE::busy)?;
+        self.engine.shift_frame(data)
+    }
+
+    fn latch(&mut self) -> Result<()> {
+        self.engine.latch_now()
+    }
+
+    fn latch_at_blank(&mut self) -> Result<bool> {
+        self.engine.latch_at_boundary()?;
+        Ok(true)
+    }
+
+    fn write_raw_iter(&mut self, data: impl Iterator<Item = u8>) -> Result<()> {
+        // Engines are handed whole frames
+        let _ = data;
+        Err(Error::Unsupported)
+    }
+
+    fn wait_idle(&mut self) -> Result<()> {
+        self.wait_for(|engine| engine.busy() || engine.latch_pending())
+    }
+}
+
+impl<ENGINE> fmt::Debug for EngineConnector<ENGINE>
+where
+    ENGINE: ShiftEngine,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EngineConnector")
+            .field("busy", &self.engine.busy())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{MockEngine, MockWriter};
+
+    #[test]
+    fn forwards_to_engine() {
+        let mut conn = EngineConnector::new(MockEngine::default());
+        conn.shift(&[1, 2, 3]).unwrap();
+        assert_eq!(conn.engine().frames, 1);
+        conn.latch().unwrap();
+        assert_eq!(conn.engine().latched_now, 1);
+        assert!(conn.latch_at_blank().unwrap());
+        assert_eq!(conn.engine().latched_at_boundary, 1);
+        assert!(!conn.engine().busy());
+    }
+
+    #[test]
+    fn waits_while_busy() {
+        let mut conn = EngineConnector::new(MockEngine::default());
+        conn.engine().busy_polls.set(3);
+        conn.shift(&[1, 2, 3]).unwrap();
+        assert_eq!(conn.engine().frames, 1);
+        assert!(!conn.engine().shifted_while_busy);
+
+        // An engine that never finishes is given up on
+        conn.engine().busy_polls.set(usize::MAX);
+        assert!(matches!(conn.shift(&[1, 2, 3]), Err(Error::Spi)));
+        assert_eq!(conn.engine().frames, 1);
+    }
+
+    #[test]
+    fn wait_idle() {
+        let mut conn = EngineConnector::new(MockEngine::default());
+        conn.engine().busy_polls.set(2);
+        conn.engine().boundary_polls.set(3);
+        conn.wait_idle().unwrap();
+        assert_eq!(conn.engine().busy_polls.get(), 0);
+        assert_eq!(conn.engine().boundary_polls.get(), 0);
+
+        conn.engine().boundary_polls.set(usize::MAX);
+        assert!(matches!(conn.wait_idle(), Err(Error::Spi)));
+    }
+
+    #[test]
+    fn debug() {
+        use core::fmt::Write as _;
+
+        let conn = EngineConnector::new(MockEngine::default());
+        let mut buf = MockWriter::default();
+        write!(buf, "{:?}", conn).unwrap();
+        assert_eq!(buf.as_str(), "EngineConnector { busy: false }");
+    }
+}
@@ -3,23 +3,82 @@
 /// during communication with the TLC5940 chip.
 ///
 #[derive(Debug)]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum Error {
     /// An attempt was made to use an unconnected function (e.g. blank
     /// while the blanking pin is not wired up)
     NotConnected,
     /// An attempt was made to access an index out of range
     OutOfRange,
+    /// An attempt was made to use a feature that the chip doesn't have
+    Unsupported,
     /// An error occurred when working with SPI
     Spi,
     /// An error occurred when working with a PIN
     Pin,
+    /// The XLAT pin could not be driven
+    Xlat,
 }
 
 /// Result wrapping the Error type
 pub type Result<T> = core::result::Result<T, Error>;
 
+#[cfg(feature = "ufmt")]
+impl ufmt::uDisplay for Error {
+    fn fmt<W>(
+        &self,
+        f: &mut ufmt::Formatter<'_, W>,
+    ) -> core::result::Result<(), W::Error>
+    where
+        W: ufmt::uWrite + ?Sized,
+    {
+        f.write_str(match self {
+            Error::NotConnected => "pin not connected",
+            Error::OutOfRange => "index out of range",
+            Error::Unsupported => "not supported by this chip",
+            Error::Spi => "SPI error",
+            Error::Pin => "pin error",
+            Error::Xlat => "XLAT pin error",
+        })
+    }
+}
+
+// Lets drivers and frameworks built on embedded-hal 1.0 categorise
+// failures. XLAT plays the part of chip select, so only its failures
+// are reported as a chip select fault.
+#[cfg(feature = "eh1")]
+impl embedded_hal_1::spi::Error for Error {
+    fn kind(&self) -> embedded_hal_1::spi::ErrorKind {
+        match self {
+            Error::Xlat => embedded_hal_1::spi::ErrorKind::ChipSelectFault,
+            _ => embedded_hal_1::spi::ErrorKind::Other,
+        }
+    }
+}
+
+#[cfg(feature = "eh1")]
+impl embedded_hal_1::digital::Error for Error {
+    fn kind(&self) -> embedded_hal_1::digital::ErrorKind {
+        embedded_hal_1::digital::ErrorKind::Other
+    }
+}
+
 /*impl<T> From<<T as embedded_hal::digital::v2::OutputPin>::Error> for Error where
 
 {
 }
 */
+
+#[cfg(all(test, feature = "eh1"))]
+mod tests {
+    use super::*;
+    use embedded_hal_1::spi::{Error as _, ErrorKind};
+
+    #[test]
+    fn spi_error_kind() {
+        assert_eq!(Error::Xlat.kind(), ErrorKind::ChipSelectFault);
+        assert_eq!(Error::Pin.kind(), ErrorKind::Other);
+        assert_eq!(Error::Spi.kind(), ErrorKind::Other);
+    }
+}
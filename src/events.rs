@@ -0,0 +1,43 @@
+//! Log events for frame shifts, mode changes and errors. These go
+//! through the `log` crate when the `std` feature is enabled, so host
+//! builds can pick them up with any logger, and compile to nothing
+//! otherwise.
+
+use core::fmt::Debug;
+
+use crate::Result;
+
+/// A frame is about to be shifted out
+pub(crate) fn frame(kind: &str, data: &[u8]) {
+    #[cfg(feature = "std")]
+    log::trace!("shifting {} byte {} frame: {:02x?}", data.len(), kind, data);
+    #[cfg(not(feature = "std"))]
+    let _ = (kind, data);
+}
+
+/// A frame of `len` bytes is about to be packed as it is shifted out
+pub(crate) fn streamed_frame(kind: &str, len: usize) {
+    #[cfg(feature = "std")]
+    log::trace!("streaming {} byte {} frame", len, kind);
+    #[cfg(not(feature = "std"))]
+    let _ = (kind, len);
+}
+
+/// The driver has switched which register the next latch writes to
+pub(crate) fn mode_change<M: Debug>(from: M, to: M) {
+    #[cfg(feature = "std")]
+    log::debug!("mode changed from {:?} to {:?}", from, to);
+    #[cfg(not(feature = "std"))]
+    let _ = (from, to);
+}
+
+/// Passes `result` through, logging it first if it is an error
+pub(crate) fn check<T>(context: &str, result: Result<T>) -> Result<T> {
+    #[cfg(feature = "std")]
+    if let Err(e) = &result {
+        log::warn!("{} failed: {:?}", context, e);
+    }
+    #[cfg(not(feature = "std"))]
+    let _ = context;
+    result
+}
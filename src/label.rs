@@ -0,0 +1,37 @@
+use core::fmt;
+
+/// A name and optional user data attached to an output
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Label {
+    /// Name used in diagnostics, e.g. "STATUS_RED"
+    pub name: &'static str,
+    /// Whatever the application wants to keep with the output
+    pub data: Option<u32>,
+}
+
+impl Label {
+    /// A label with just a name
+    pub const fn new(name: &'static str) -> Self {
+        Label { name, data: None }
+    }
+}
+
+///
+/// Displays an output by its label if it has one, or as "channel N"
+/// otherwise, so that diagnostics can say "STATUS_RED open" rather than
+/// "channel 11 open".
+///
+#[derive(Clone, Copy, Debug)]
+pub struct ChannelName<'a> {
+    pub(crate) output: u8,
+    pub(crate) label: Option<&'a Label>,
+}
+
+impl fmt::Display for ChannelName<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.label {
+            Some(label) => f.write_str(label.name),
+            None => write!(f, "channel {}", self.output),
+        }
+    }
+}
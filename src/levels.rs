@@ -0,0 +1,106 @@
+use core::marker::PhantomData;
+
+use crate::packing::clamp_level;
+use crate::variant::ChipVariant;
+use crate::{Error, Result};
+#[cfg(feature = "color")]
+use crate::{Pixel, PixelLayout};
+
+/// The grayscale levels a driver holds for its outputs, and where
+/// pixels go on them. Shared by all of the drivers.
+pub(crate) struct Levels<CHIP, const CHANNELS: usize> {
+    /// Level of each output, already cut down to the chip's grayscale
+    /// width
+    pub(crate) values: [u16; CHANNELS],
+    /// Where `set_pixels` puts each pixel
+    #[cfg(feature = "color")]
+    layout: PixelLayout,
+    chip: PhantomData<CHIP>,
+}
+
+impl<CHIP, const CHANNELS: usize> Levels<CHIP, CHANNELS>
+where
+    CHIP: ChipVariant,
+{
+    /// All outputs off, with pixels starting at output 0
+    pub(crate) fn new() -> Self {
+        Levels {
+            values: [0; CHANNELS],
+            #[cfg(feature = "color")]
+            layout: PixelLayout::default(),
+            chip: PhantomData,
+        }
+    }
+
+    /// Stores a level for `output`, dropping any bits the chip can't use
+    pub(crate) fn set(&mut self, output: u8, level: u16) -> Result<()> {
+        let slot = self
+            .values
+            .get_mut(output as usize)
+            .ok_or(Error::OutOfRange)?;
+        *slot = clamp_level::<CHIP>(level);
+        Ok(())
+    }
+
+    /// Stores a level for every output, dropping any bits the chip
+    /// can't use
+    pub(crate) fn set_all(&mut self, levels: [u16; CHANNELS]) {
+        for (slot, level) in self.values.iter_mut().zip(levels) {
+            *slot = clamp_level::<CHIP>(level);
+        }
+    }
+
+    /// Sets where `set_pixels` puts each pixel
+    #[cfg(feature = "color")]
+    pub(crate) fn set_layout(&mut self, layout: PixelLayout) {
+        self.layout = layout;
+    }
+
+    /// Stores levels from a sequence of pixels. Each pixel takes up
+    /// `P::CHANNELS` consecutive outputs, placed according to the
+    /// layout, and its 12-bit levels are stretched or cut down to the
+    /// chip's grayscale width unless the pixel is `NATIVE`.
+    #[cfg(feature = "color")]
+    pub(crate) fn set_pixels<P, I>(&mut self, pixels: I) -> Result<()>
+    where
+        P: Pixel,
+        I: IntoIterator<Item = P>,
+    {
+        // The order has to place every channel of the pixel exactly once
+        let order = self.layout.order;
+        if !order.is_empty()
+            && (order.len() != P::CHANNELS
+                || order.iter().enumerate().any(|(idx, pos)| {
+                    *pos as usize >= P::CHANNELS || order[..idx].contains(pos)
+                }))
+        {
+            return Err(Error::OutOfRange);
+        }
+
+        let mut first = self.layout.start as usize;
+        for pixel in pixels {
+            for idx in 0..P::CHANNELS {
+                let level = if P::NATIVE {
+                    pixel.level(idx)
+                } else {
+                    let level = (pixel.level(idx) & 0x0fff) as u32;
+                    let level = if CHIP::GS_BITS > 12 {
+                        let extra = CHIP::GS_BITS - 12;
+                        (level << extra) | (level >> (12 - extra))
+                    } else {
+                        level >> (12 - CHIP::GS_BITS)
+                    };
+                    level as u16
+                };
+                let output =
+                    first + order.get(idx).map_or(idx, |pos| *pos as usize);
+                if output > u8::MAX as usize {
+                    return Err(Error::OutOfRange);
+                }
+                self.set(output as u8, level)?;
+            }
+            first += P::CHANNELS;
+        }
+        Ok(())
+    }
+}
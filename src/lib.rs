@@ -1,6 +1,12 @@
-#![no_std]
+#![cfg_attr(not(feature = "std"), no_std)]
 
-use embedded_hal::blocking::spi::Write;
+use core::convert::Infallible;
+use core::fmt;
+use core::marker::PhantomData;
+
+#[cfg(feature = "diag")]
+use embedded_hal::blocking::delay::DelayUs;
+use embedded_hal::blocking::spi::{Transfer, Write};
 use embedded_hal::digital::v2::{InputPin, OutputPin};
 
 pub mod connectors;
@@ -9,9 +15,63 @@ use connectors::*;
 pub mod unconnected;
 pub use unconnected::Unconnected;
 
+pub mod engine;
+pub use engine::{EngineConnector, ShiftEngine};
+
 pub mod error;
 pub use error::{Error, Result};
 
+#[cfg(feature = "diag")]
+pub mod label;
+#[cfg(feature = "diag")]
+pub use label::{ChannelName, Label};
+
+#[cfg(feature = "color")]
+pub mod pixel;
+#[cfg(feature = "color")]
+pub use pixel::{Pixel, PixelLayout};
+
+pub mod pwm_blank;
+pub use pwm_blank::PwmBlank;
+
+pub mod tlc59711;
+pub use tlc59711::TLC59711;
+
+pub mod tlc5955;
+pub use tlc5955::TLC5955;
+
+#[cfg(feature = "diag")]
+pub mod xerr;
+#[cfg(feature = "diag")]
+pub use xerr::{ErrorCause, XerrFilter};
+
+#[cfg(feature = "effects")]
+pub mod zone;
+#[cfg(feature = "effects")]
+use zone::Zones;
+#[cfg(feature = "effects")]
+pub use zone::{Zone, ZoneId};
+
+pub mod variant;
+use variant::{ChipVariant, ModeSwitch};
+
+#[cfg(test)]
+mod mock;
+
+mod packing;
+use packing::*;
+
+mod levels;
+use levels::Levels;
+
+mod debug;
+use debug::LevelSummary;
+
+mod events;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum OperatingMode {
     /// Grayscale PWM Mode
     GrayscalePWM,
@@ -21,6 +81,22 @@ pub enum OperatingMode {
     Eeprom,
 }
 
+/// When grayscale frames take effect
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum UpdateStrategy {
+    /// Latch each frame as soon as it has been shifted. New levels show
+    /// up straight away, but may land part way through a grayscale
+    /// cycle and cause a brief glitch.
+    #[default]
+    Immediate,
+    /// Hold each frame in the shift register until the next call to
+    /// `on_blank_interrupt`, so that it takes effect on a grayscale
+    /// cycle boundary. Adds up to one cycle of latency.
+    LatchAtBlank,
+}
+
 ///
 /// Handles communication with the MAX7219
 /// chip for segmented displays. Each display can be
@@ -28,15 +104,34 @@ pub enum OperatingMode {
 /// a single connection. The actual connection interface
 /// is selected via constructor functions.
 ///
-pub struct TLC5940<CONNECTOR, BLANK, XERR>
+/// The members of the family are selected by `CHIP`, see the `TLC5940`
+/// and `TLC5941` aliases. Other chips with plain grayscale frames can be
+/// driven as well, such as the TLC5947 through the `TLC5947` alias (see
+/// `ChipVariant`). `CHANNELS`
+/// is the total number of outputs driven, which has to be a multiple of
+/// the chip's channel count.
+///
+pub struct TLC594x<CONNECTOR, VPRG, BLANK, XERR, CHIP, const CHANNELS: usize>
 where
+    VPRG: OutputPin,
     BLANK: OutputPin,
-    XERR: OutputPin,
+    XERR: InputPin,
+    CHIP: ChipVariant,
 {
     connector: CONNECTOR,
+    chip: PhantomData<CHIP>,
+
+    /// Mode select. When set LOW the shifted data is latched into the
+    /// grayscale register, when set HIGH it goes to the dot correction
+    /// register
+    vprg_pin: VPRG,
+    /// The mode that `vprg_pin` currently selects
+    mode: OperatingMode,
 
     /// Output enable/blanking. When set HIGH all outputs are disabled
     blank_pin: BLANK,
+    /// Whether `blank_pin` was last set HIGH
+    blanked: bool,
     /// `xerr` is an open-drain output that goes low if the Thermal Error
     /// Flag or LED Open Detection events trigger. Needs a pullup, active
     /// LOW
@@ -44,11 +139,29 @@ where
     /// DOT correction values. Each channel should be in the 0-63 range
     /// as the TLC5940 accepts 6-bit values. The upper 2 bits of each
     /// value here are ignored when pushing changes to the chip.
-    dot_correction: [u8; 16],
-    /// Brightness values for each channel. Each channel should be in the
-    /// 0-4095 range as the TLC5940 uses 12-bit PWM. The upper 4 bits of
-    /// each value here are ignored when pushing changes to the chip.
-    grayscale_values: [u16; 16],
+    dot_correction: [u8; CHANNELS],
+    /// Whether `dot_correction` matches what was last latched into the
+    /// chip's dot correction register
+    dc_pushed: bool,
+    /// When grayscale frames are latched
+    update_strategy: UpdateStrategy,
+    /// Whether a grayscale frame has been shifted but not yet latched
+    latch_pending: bool,
+    /// Whether the next grayscale latch is the first since switching
+    /// from dot correction, which needs an extra SCLK pulse after XLAT
+    extra_sclk_pending: bool,
+    /// Named groups of outputs
+    #[cfg(feature = "effects")]
+    zones: Zones,
+    /// Debounces the XERR line
+    #[cfg(feature = "diag")]
+    xerr_filter: XerrFilter,
+    /// Names and user data for each output
+    #[cfg(feature = "diag")]
+    labels: [Option<Label>; CHANNELS],
+    /// Brightness values for each channel, in the 0-4095 range as the
+    /// TLC5940 uses 12-bit PWM
+    levels: Levels<CHIP, CHANNELS>,
     // /// Status returned from the device
     //status: StatusInformation,
 }
@@ -56,11 +169,71 @@ where
 // /// Status information returned from the chip
 //pub struct StatusInformation;
 
-impl<CONNECTOR, BLANK, XERR> TLC5940<CONNECTOR, BLANK, XERR>
+/// TLC5940 driver
+pub type TLC5940<CONNECTOR, VPRG, BLANK, XERR> = TLC594x<
+    CONNECTOR,
+    VPRG,
+    BLANK,
+    XERR,
+    variant::Tlc5940,
+    { variant::Tlc5940::CHANNELS },
+>;
+
+/// TLC5941 driver. The TLC5941 has no dot correction EEPROM, so the
+/// stored dot correction values are pushed when the driver is
+/// constructed and VPRG must be connected.
+pub type TLC5941<CONNECTOR, VPRG, BLANK, XERR> = TLC594x<
+    CONNECTOR,
+    VPRG,
+    BLANK,
+    XERR,
+    variant::Tlc5941,
+    { variant::Tlc5941::CHANNELS },
+>;
+
+/// TLC5947 driver. The TLC5947 has no VPRG, XERR or dot correction, so
+/// those are left `Unconnected` and dot correction writes return
+/// `Error::Unsupported`. It runs its own grayscale clock. Chained
+/// TLC5947s are driven through `TLC594x` with 24 `CHANNELS` per chip.
+pub type TLC5947<CONNECTOR, BLANK> = TLC594x<
+    CONNECTOR,
+    Unconnected,
+    BLANK,
+    Unconnected,
+    variant::Tlc5947,
+    { variant::Tlc5947::CHANNELS },
+>;
+
+/// TLC5940 on a hardware SPI bus with XLAT on a GPIO, as built by
+/// `from_spi_cs`. VPRG and XERR are left unconnected.
+pub type Tlc5940HwSpi<SPI, XLAT, BLANK> =
+    TLC5940<SpiConnectorSW<SPI, XLAT>, Unconnected, BLANK, Unconnected>;
+
+/// TLC5940 with bit-banged SIN, XLAT and SCLK, as built by `from_pins`.
+/// VPRG and XERR are left unconnected.
+pub type Tlc5940BitBang<DATA, XLAT, SCK, BLANK> =
+    TLC5940<PinConnector<DATA, XLAT, SCK>, Unconnected, BLANK, Unconnected>;
+
+/// Chain of TLC5940s on a hardware SPI bus with XLAT on a GPIO.
+/// `CHANNELS` is the total number of outputs, i.e. 16 for each chip in
+/// the chain.
+pub type Tlc5940Chain<SPI, XLAT, BLANK, const CHANNELS: usize> = TLC594x<
+    SpiConnectorSW<SPI, XLAT>,
+    Unconnected,
+    BLANK,
+    Unconnected,
+    variant::Tlc5940,
+    CHANNELS,
+>;
+
+impl<CONNECTOR, VPRG, BLANK, XERR, CHIP, const CHANNELS: usize>
+    TLC594x<CONNECTOR, VPRG, BLANK, XERR, CHIP, CHANNELS>
 where
     CONNECTOR: Connector,
+    VPRG: OutputPin,
     BLANK: OutputPin,
-    XERR: OutputPin,
+    XERR: InputPin,
+    CHIP: ChipVariant,
 {
     ///
     /// Blanks the outputs.
@@ -82,15 +255,115 @@ where
         }*/
 
         if is_blank {
-            //TODO Sort the Error type conversions out so that ? can be
-            // used to propagate the error
-            self.blank_pin.set_high();
+            self.blank_pin.set_high().map_err(|_| Error::Pin)?;
         } else {
-            self.blank_pin.set_low();
+            self.blank_pin.set_low().map_err(|_| Error::Pin)?;
         }
+        self.blanked = is_blank;
         Ok(())
     }
 
+    /// Whether the outputs were last blanked with `blank`
+    pub fn is_blanked(&self) -> bool {
+        self.blanked
+    }
+
+    ///
+    /// Reads the XERR line, without any filtering. See `tick` for a
+    /// debounced reading with the `diag` feature.
+    ///
+    /// Returns `true` if the chip is reporting a thermal error or an
+    /// open LED.
+    ///
+    /// # Errors
+    ///
+    /// * `Error::Pin` if the XERR pin could not be read (e.g. it is
+    ///   `Unconnected`)
+    ///
+    pub fn error_flag(&self) -> Result<bool> {
+        // XERR is active LOW
+        self.xerr_pin.is_low().map_err(|_| Error::Pin)
+    }
+
+    /// Sets how XERR is debounced by `tick`, clearing any error it has
+    /// already reported
+    #[cfg(feature = "diag")]
+    pub fn set_xerr_filter(&mut self, filter: XerrFilter) {
+        self.xerr_filter = filter;
+    }
+
+    ///
+    /// Samples XERR through the filter set with `set_xerr_filter`. Call
+    /// this at a regular interval, e.g. from a timer interrupt.
+    ///
+    /// Returns `true` if the filtered error is asserted.
+    ///
+    /// # Errors
+    ///
+    /// * `Error::Pin` if the XERR pin could not be read
+    ///
+    #[cfg(feature = "diag")]
+    pub fn tick(&mut self) -> Result<bool> {
+        let mut asserted = 0;
+        for _ in 0..self.xerr_filter.samples() {
+            asserted += self.error_flag()? as u8;
+        }
+        Ok(self.xerr_filter.update(asserted))
+    }
+
+    /// The filtered XERR state as of the last `tick`
+    #[cfg(feature = "diag")]
+    pub fn error_asserted(&self) -> bool {
+        self.xerr_filter.active()
+    }
+
+    ///
+    /// Works out why XERR is asserted. Blanking the outputs clears LED
+    /// Open Detection but not the Thermal Error Flag, so BLANK is
+    /// briefly set HIGH and XERR read again once it has settled. BLANK
+    /// is then put back the way it was.
+    ///
+    /// Returns `None` if XERR isn't asserted.
+    ///
+    /// # Arguments
+    ///
+    /// * `delay` - used to wait for XERR to settle
+    /// * `settle_us` - how long to wait after setting BLANK HIGH. XERR
+    ///   is open-drain, so this has to cover its pull-up charging the
+    ///   line, and with `PwmBlank` at least one timer period for the new
+    ///   duty to take effect.
+    ///
+    /// # Errors
+    ///
+    /// * `Error::Pin` if XERR could not be read or BLANK could not be
+    ///   driven
+    ///
+    #[cfg(feature = "diag")]
+    pub fn classify_error<D>(
+        &mut self,
+        delay: &mut D,
+        settle_us: u32,
+    ) -> Result<Option<ErrorCause>>
+    where
+        D: DelayUs<u32>,
+    {
+        if !self.error_flag()? {
+            return Ok(None);
+        }
+
+        let was_blanked = self.blanked;
+        self.blank(true)?;
+        delay.delay_us(settle_us);
+        let thermal = self.error_flag();
+        self.blank(was_blanked)?;
+
+        Ok(Some(if thermal? {
+            ErrorCause::Thermal
+        } else {
+            ErrorCause::LedOpen
+        }))
+    }
+
     /*/// Read status information from the device
     pub fn read_status(&mut self) -> Result<&StatusInformation> {
         // Get status from device
@@ -98,184 +371,1899 @@ where
         todo!();
     }*/
 
+    /// Mutable access to the connector, e.g. to change its bit order
+    pub fn connector_mut(&mut self) -> &mut CONNECTOR {
+        &mut self.connector
+    }
+
     /// Store an intensity value
     pub fn set_level(&mut self, output: u8, level: u16) -> Result<()> {
-        // There can only be 16 outputs
-        if output >= 16 {
-            return Err(Error::OutOfRange);
-        }
-
         // Ignore out of range greyscale values by just taking the lower
         // 12 bits
-        self.grayscale_values[output as usize] = level & 0x0fff;
-        Ok(())
+        self.levels.set(output, level)
     }
 
     /// Store all levels at the same time
-    pub fn set_levels(&mut self, levels: [u16; 16]) -> Result<()> {
-        for (idx, level) in levels.iter().enumerate() {
-            self.set_level(idx as u8, *level)?;
-        }
+    pub fn set_levels(&mut self, levels: [u16; CHANNELS]) -> Result<()> {
+        self.levels.set_all(levels);
         Ok(())
     }
 
+    /// Choose where `set_pixels` puts each pixel. By default pixels
+    /// start at output 0 with their channels in their own order.
+    #[cfg(feature = "color")]
+    pub fn set_pixel_layout(&mut self, layout: PixelLayout) {
+        self.levels.set_layout(layout);
+    }
+
+    ///
+    /// Store levels from a sequence of pixels. Each pixel takes up
+    /// `P::CHANNELS` consecutive outputs, placed according to the layout
+    /// set with `set_pixel_layout`. Outputs outside the pixels are left
+    /// unchanged.
+    ///
+    /// # Errors
+    ///
+    /// * `Error::OutOfRange` if the pixels don't fit in the outputs, or
+    ///   the layout's order doesn't place each of a pixel's channels
+    ///   exactly once. Pixels that did fit are still stored.
+    ///
+    #[cfg(feature = "color")]
+    pub fn set_pixels<P, I>(&mut self, pixels: I) -> Result<()>
+    where
+        P: Pixel,
+        I: IntoIterator<Item = P>,
+    {
+        self.levels.set_pixels(pixels)
+    }
+
+    /// Store levels from a sequence of pixels and transfer them to the
+    /// chip, see `set_pixels`
+    #[cfg(feature = "color")]
+    pub fn write_pixels<P, I>(&mut self, pixels: I) -> Result<()>
+    where
+        P: Pixel,
+        I: IntoIterator<Item = P>,
+    {
+        self.set_pixels(pixels)?;
+        self.update()
+    }
+
     /// Transfer the stored leves to the chip
     pub fn update(&mut self) -> Result<()> {
-        // Pack the intensity values into a 24-byte array
-        let mut packed = [0_u8; 6];
+        let levels = self.frame_levels();
+        self.write_grayscale_raw(&levels)
+    }
 
-        // Write it on the wire
-        self.connector.write_raw(&packed);
-        todo!();
+    ///
+    /// Transfer the stored levels to the chip like `update`, but pack
+    /// them as they are shifted out through `Connector::write_raw_iter`
+    /// instead of into a buffer first. Meant for long chains, where the
+    /// packed frame would take up a lot of RAM.
+    ///
+    /// # Errors
+    ///
+    /// * `Error::Unsupported` - the connector can't stream, or the update
+    ///   strategy is `LatchAtBlank`, which needs the frame shifted
+    ///   without being latched
+    ///
+    pub fn update_streamed(&mut self) -> Result<()> {
+        if self.update_strategy == UpdateStrategy::LatchAtBlank {
+            return Err(Error::Unsupported);
+        }
+        events::check(
+            "mode change",
+            self.set_mode(OperatingMode::GrayscalePWM),
+        )?;
+
+        // Levels are looked up as the frame is shifted out, after any
+        // zone settings, the same as `frame_levels` would give them
+        let levels = &self.levels.values;
+        #[cfg(feature = "effects")]
+        let zones = &self.zones;
+        #[cfg(feature = "effects")]
+        let level = |channel: usize| zones.level(channel, levels[channel]);
+        #[cfg(not(feature = "effects"))]
+        let level = |channel: usize| levels[channel];
+        let len = Self::frame_len(CHIP::GS_FRAME_LEN);
+        let frame = GrayscaleBytes::<CHIP, _>::new(CHANNELS, len, level)?;
+        events::streamed_frame("grayscale", len);
+        // Shifting overwrites any frame still waiting to be latched
+        self.latch_pending = false;
+        events::check("grayscale write", self.connector.write_raw_iter(frame))?;
+        self.finish_grayscale_latch()
     }
 
-    /// Set the dot correction values
-    pub fn set_dot_correction(&mut self) -> Result<()> {
-        // Pack the intensity values into a 24-byte array
-        let mut packed = [0_u8; 6];
+    ///
+    /// Adds a zone, enabled, so that its outputs can be set together.
+    ///
+    /// # Errors
+    ///
+    /// * `Error::OutOfRange` if the zone refers to an output the driver
+    ///   doesn't have, its name is already in use or `zone::MAX_ZONES`
+    ///   zones have been added
+    ///
+    #[cfg(feature = "effects")]
+    pub fn add_zone(&mut self, zone: Zone) -> Result<ZoneId> {
+        if zone.channels.iter().any(|c| *c as usize >= CHANNELS) {
+            return Err(Error::OutOfRange);
+        }
+        self.zones.add(zone)
+    }
 
-        // do the thing to make it accept dot correction
+    /// Look up a zone by name
+    #[cfg(feature = "effects")]
+    pub fn zone_id(&self, name: &str) -> Option<ZoneId> {
+        self.zones.find(name)
+    }
 
-        // Write it on the wire
-        self.connector.write_raw(&packed);
-        todo!();
+    /// Store the same level for every output in a zone
+    #[cfg(feature = "effects")]
+    pub fn set_zone(&mut self, zone: ZoneId, level: u16) -> Result<()> {
+        let entry = *self.zones.get(zone)?;
+        for channel in entry.zone.channels {
+            self.set_level(*channel, level)?;
+        }
+        Ok(())
     }
 
-    // internal constructor, users should call ::from_pins or ::from_spi
-    fn new(
-        connector: CONNECTOR,
-        blank_pin: BLANK,
-        xerr_pin: XERR,
-    ) -> Result<Self> {
-        let mut tlc5940 = Self {
-            connector,
-            blank_pin,
-            xerr_pin,
-            dot_correction: [0; 16],
-            grayscale_values: [0; 16],
-        };
+    ///
+    /// Moves every output in a zone one step towards `target`. Call this
+    /// once per frame, followed by `update`, to fade the zone in or out.
+    ///
+    /// Returns `true` once every output has reached `target`.
+    ///
+    /// # Arguments
+    ///
+    /// * `zone` - the zone to fade
+    /// * `target` - the level to fade to
+    /// * `step` - the most any output changes by in one call
+    ///
+    #[cfg(feature = "effects")]
+    pub fn fade_zone(
+        &mut self,
+        zone: ZoneId,
+        target: u16,
+        step: u16,
+    ) -> Result<bool> {
+        let entry = *self.zones.get(zone)?;
+        let target = clamp_level::<CHIP>(target);
+        let mut done = true;
+        for channel in entry.zone.channels {
+            let level = &mut self.levels.values[*channel as usize];
+            *level = if *level < target {
+                level.saturating_add(step).min(target)
+            } else {
+                level.saturating_sub(step).max(target)
+            };
+            done &= *level == target;
+        }
+        Ok(done)
+    }
 
-        tlc5940.init()?;
-        Ok(tlc5940)
+    ///
+    /// Enables or disables a zone. The outputs of a disabled zone are
+    /// sent as off by `update`, but keep their stored levels so that
+    /// they come back as they were when the zone is enabled again.
+    ///
+    #[cfg(feature = "effects")]
+    pub fn enable_zone(&mut self, zone: ZoneId, enabled: bool) -> Result<()> {
+        self.zones.get_mut(zone)?.enabled = enabled;
+        Ok(())
     }
 
-    fn init(&mut self) -> Result<()> {
-        // Probably don't need this function
-        //self.blank(false);
+    /// Whether a zone is enabled
+    #[cfg(feature = "effects")]
+    pub fn zone_enabled(&self, zone: ZoneId) -> Result<bool> {
+        Ok(self.zones.get(zone)?.enabled)
+    }
 
+    /// Attach a label to an output, or remove it with `None`
+    #[cfg(feature = "diag")]
+    pub fn set_label(
+        &mut self,
+        output: u8,
+        label: Option<Label>,
+    ) -> Result<()> {
+        let slot = self
+            .labels
+            .get_mut(output as usize)
+            .ok_or(Error::OutOfRange)?;
+        *slot = label;
         Ok(())
     }
-}
 
-impl<DATA, CS, SCK, BLANK, XERR>
-    TLC5940<PinConnector<DATA, CS, SCK>, BLANK, XERR>
-where
-    DATA: OutputPin,
-    CS: OutputPin,
-    SCK: OutputPin,
-    BLANK: OutputPin,
-    XERR: OutputPin,
-{
+    /// Get the label attached to an output
+    #[cfg(feature = "diag")]
+    pub fn label(&self, output: u8) -> Option<&Label> {
+        self.labels.get(output as usize)?.as_ref()
+    }
+
+    /// Name of an output for use in diagnostics, see `ChannelName`
+    #[cfg(feature = "diag")]
+    pub fn channel_name(&self, output: u8) -> ChannelName<'_> {
+        ChannelName {
+            output,
+            label: self.label(output),
+        }
+    }
+
     ///
-    /// Construct a new MAX7219 driver instance from DATA, CS and SCK pins.
+    /// Writes the stored level of every output, one per line and named
+    /// with `channel_name`, followed by the state of each zone.
     ///
-    /// # Arguments
+    #[cfg(feature = "diag")]
+    pub fn dump_state<W: fmt::Write>(&self, w: &mut W) -> fmt::Result {
+        for (output, level) in self.levels.values.iter().enumerate() {
+            writeln!(w, "{}: {}", self.channel_name(output as u8), level)?;
+        }
+        #[cfg(feature = "effects")]
+        self.zones.dump(w)?;
+        Ok(())
+    }
+
     ///
-    /// * `displays` - number of displays connected in series
-    /// * `data` - the MOSI/DATA PIN used to send data through to the display set to output mode
-    /// * `cs` - the CS PIN used to LOAD register on the display set to output mode
-    /// * `sck` - the SCK clock PIN used to drive the clock set to output mode
+    /// Sets the highest level `update` sends for any output in a zone,
+    /// e.g. to keep indicators dim at night. The stored levels are left
+    /// alone so raising the limit again restores them.
+    ///
+    #[cfg(feature = "effects")]
+    pub fn set_zone_limit(&mut self, zone: ZoneId, ceiling: u16) -> Result<()> {
+        self.zones.get_mut(zone)?.ceiling = ceiling;
+        Ok(())
+    }
+
+    ///
+    /// Sets the master scale for a zone, applied by `update` before the
+    /// zone's limit. 255 gives full output, 0 turns the zone off.
+    ///
+    #[cfg(feature = "effects")]
+    pub fn set_zone_master(&mut self, zone: ZoneId, master: u8) -> Result<()> {
+        self.zones.get_mut(zone)?.master = master;
+        Ok(())
+    }
+
+    /// Set the dot correction values
+    pub fn set_dot_correction(&mut self) -> Result<()> {
+        let values = self.dot_correction;
+        self.write_dc_raw(&values)?;
+        self.dc_pushed = true;
+        Ok(())
+    }
+
+    /// Store a dot correction value. Call `set_dot_correction` to push
+    /// the stored values to the chip.
+    pub fn set_dot_correction_level(
+        &mut self,
+        output: u8,
+        value: u8,
+    ) -> Result<()> {
+        // Dot correction is 6 bits wide
+        store_dot_correction::<CHIP>(&mut self.dot_correction, output, value)?;
+        self.dc_pushed = false;
+        Ok(())
+    }
+
+    /// Get the stored dot correction value for a channel
+    pub fn dot_correction(&self, output: u8) -> Result<u8> {
+        self.dot_correction
+            .get(output as usize)
+            .copied()
+            .ok_or(Error::OutOfRange)
+    }
+
+    /// Get the stored dot correction values for all channels
+    pub fn dot_correction_all(&self) -> &[u8; CHANNELS] {
+        &self.dot_correction
+    }
+
+    /// Whether the stored dot correction values have been pushed to the
+    /// chip since they were last changed
+    pub fn dot_correction_pushed(&self) -> bool {
+        self.dc_pushed
+    }
+
+    ///
+    /// Choose whether grayscale frames are latched as soon as they have
+    /// been shifted or held until the next `on_blank_interrupt`, trading
+    /// latency against glitches. Switching to `Immediate` doesn't latch
+    /// a frame that is already waiting; the next frame sent replaces it.
+    ///
+    /// Connectors that latch every frame as it is shifted, such as the
+    /// one built by `from_spi`, can't hold a frame back, so with them
+    /// `update` returns `Error::Unsupported` under `LatchAtBlank`.
+    ///
+    pub fn set_update_strategy(&mut self, strategy: UpdateStrategy) {
+        self.update_strategy = strategy;
+    }
+
+    /// The strategy used for grayscale frames
+    pub fn update_strategy(&self) -> UpdateStrategy {
+        self.update_strategy
+    }
+
+    /// Whether a grayscale frame is waiting for `on_blank_interrupt`
+    pub fn latch_pending(&self) -> bool {
+        self.latch_pending
+    }
+
+    ///
+    /// Latches the grayscale frame staged by `update`, if there is one.
+    /// Call this from the interrupt that fires at the end of each
+    /// grayscale cycle, while BLANK is high.
+    ///
+    /// Returns `true` if a frame was latched.
     ///
     /// # Errors
     ///
-    /// * `DataError` - returned in case there was an error during data transfer
+    /// * `Error::Xlat` if XLAT could not be driven
     ///
-    pub fn from_pins(
-        data: DATA,
-        cs: CS,
-        sck: SCK,
-        blank_pin: BLANK,
-        xerr_pin: XERR,
-    ) -> Result<Self> {
-        TLC5940::new(PinConnector::new(data, cs, sck), blank_pin, xerr_pin)
+    pub fn on_blank_interrupt(&mut self) -> Result<bool> {
+        if !self.latch_pending {
+            return Ok(false);
+        }
+        events::check("deferred latch", self.connector.latch())?;
+        self.latch_pending = false;
+        self.finish_grayscale_latch()?;
+        Ok(true)
     }
-}
 
-impl<SPI, BLANK, XERR> TLC5940<SpiConnector<SPI>, BLANK, XERR>
-where
-    SPI: Write<u8>,
-    BLANK: OutputPin,
-    XERR: OutputPin,
-{
     ///
-    /// Construct a new MAX7219 driver instance from pre-existing SPI in full hardware mode.
-    /// The SPI will control CS (LOAD) line according to it's internal mode set.
-    /// If you need the CS line to be controlled manually use MAX7219::from_spi_cs
+    /// Runs a simple refresh loop for firmware without timers or an
+    /// RTOS, with GSCLK bit-banged on a GPIO. Each frame `frame_fn` is
+    /// given the frame number and the stored levels to change, then the
+    /// levels are sent with `update` and `run_frame` keeps the grayscale
+    /// cycle going for the rest of the frame. With
+    /// `UpdateStrategy::LatchAtBlank` the frame is latched while BLANK
+    /// is high at the start of its first cycle.
     ///
-    /// * `NOTE` - make sure the SPI is initialized in MODE_0 with max 10 Mhz frequency.
+    /// Only returns if something goes wrong.
     ///
     /// # Arguments
     ///
-    /// * `displays` - number of displays connected in series
-    /// * `spi` - the SPI interface initialized with MOSI, MISO(unused) and CLK
+    /// * `gsclk` - the GSCLK PIN set to output mode
+    /// * `cycles_per_frame` - grayscale cycles run for each frame. The
+    ///   frame rate is the GSCLK rate the MCU manages divided by
+    ///   `2^GS_BITS * cycles_per_frame`.
+    /// * `frame_fn` - produces each frame. Returning an error stops the
+    ///   loop.
     ///
     /// # Errors
     ///
-    /// * `DataError` - returned in case there was an error during data transfer
+    /// * `Error::OutOfRange` if `cycles_per_frame` is 0
+    /// * Any error from `frame_fn`, from sending a frame or from
+    ///   `run_frame`
     ///
-    pub fn from_spi(
-        displays: usize,
-        spi: SPI,
-        blank_pin: BLANK,
-        xerr_pin: XERR,
-    ) -> Result<Self> {
-        TLC5940::new(SpiConnector::new(displays, spi), blank_pin, xerr_pin)
+    pub fn run_refresh_loop<GSCLK, F>(
+        &mut self,
+        gsclk: &mut GSCLK,
+        cycles_per_frame: u32,
+        mut frame_fn: F,
+    ) -> Result<Infallible>
+    where
+        GSCLK: OutputPin,
+        F: FnMut(u32, &mut [u16; CHANNELS]) -> Result<()>,
+    {
+        if cycles_per_frame == 0 {
+            return Err(Error::OutOfRange);
+        }
+
+        let mut frame = 0_u32;
+        loop {
+            frame_fn(frame, &mut self.levels.values)?;
+            self.update()?;
+            // Without BLANK pulsing at the end of each cycle the outputs
+            // would go dark after the first one
+            for _ in 0..cycles_per_frame {
+                self.run_frame(gsclk)?;
+            }
+            frame = frame.wrapping_add(1);
+        }
     }
-}
 
-impl<SPI, CS, BLANK, XERR> TLC5940<SpiConnectorSW<SPI, CS>, BLANK, XERR>
-where
-    SPI: Write<u8>,
-    CS: OutputPin,
-    BLANK: OutputPin,
-    XERR: OutputPin,
-{
     ///
-    /// Construct a new TLC5940 driver instance from pre-existing SPI and CS pin
-    /// set to output. This version of the connection uses the CS pin manually
-    /// to avoid issues with how the CS mode is handled in hardware SPI implementations.
+    /// Runs one grayscale cycle with GSCLK bit-banged on a GPIO, for
+    /// MCUs with no timer to spare. Pulses BLANK, latching any frame
+    /// staged with `UpdateStrategy::LatchAtBlank` while it is high, then
+    /// clocks out the `2^GS_BITS` (4096 on the TLC5940) GSCLK pulses of
+    /// the cycle. Blocks for the whole cycle, and has to be called in a
+    /// loop for the outputs to stay on.
     ///
-    /// * `NOTE` - make sure the SPI is initialized in MODE_0 with max 10 Mhz frequency.
+    /// # Errors
+    ///
+    /// * `Error::Pin` if BLANK or GSCLK could not be driven
+    /// * `Error::Xlat` if XLAT could not be driven
+    ///
+    pub fn run_frame<GSCLK>(&mut self, gsclk: &mut GSCLK) -> Result<()>
+    where
+        GSCLK: OutputPin,
+    {
+        self.blank(true)?;
+        self.on_blank_interrupt()?;
+        self.blank(false)?;
+
+        for _ in 0..1_u32 << CHIP::GS_BITS {
+            gsclk.set_high().map_err(|_| Error::Pin)?;
+            gsclk.set_low().map_err(|_| Error::Pin)?;
+        }
+        Ok(())
+    }
+
+    ///
+    /// Packs and latches a full set of grayscale values, bypassing the
+    /// levels stored in the driver. Intended for applications that
+    /// compute their own frames and only need them put on the wire.
+    /// With `UpdateStrategy::LatchAtBlank` the frame is only shifted,
+    /// and latched by the next `on_blank_interrupt`.
     ///
     /// # Arguments
     ///
-    /// * `displays` - number of displays connected in series
-    /// * `spi` - the SPI interface initialized with MOSI, MISO(unused) and CLK
-    /// * `cs` - the CS PIN used to LOAD register on the display set to output mode
+    /// * `levels` - one value per channel, starting at channel 0. Only
+    ///   the lower 12 bits of each value are used.
     ///
     /// # Errors
     ///
-    /// * `DataError` - returned in case there was an error during data transfer
+    /// * `Error::OutOfRange` if `levels` does not hold exactly `CHANNELS`
+    ///   values
+    /// * `Error::Unsupported` with `UpdateStrategy::LatchAtBlank` if the
+    ///   connector latches every frame as it is shifted
     ///
-    pub fn from_spi_cs(
-        displays: usize,
-        spi: SPI,
-        cs: CS,
-        blank_pin: BLANK,
-        xerr_pin: XERR,
-    ) -> Result<Self> {
-        TLC5940::new(
-            SpiConnectorSW::new(displays, spi, cs),
-            blank_pin,
-            xerr_pin,
-        )
+    pub fn write_grayscale_raw(&mut self, levels: &[u16]) -> Result<()> {
+        if levels.len() != CHANNELS {
+            return Err(Error::OutOfRange);
+        }
+        if self.update_strategy == UpdateStrategy::LatchAtBlank
+            && !self.connector.can_defer_latch()
+        {
+            return Err(Error::Unsupported);
+        }
+
+        // Two bytes per channel is enough for up to 16 bits of grayscale
+        let mut packed = [[0_u8; 2]; CHANNELS];
+        let packed = &mut packed.as_flattened_mut()
+            [..Self::frame_len(CHIP::GS_FRAME_LEN)];
+        pack_grayscale::<CHIP>(&mut BitWriter::new(packed), levels)?;
+
+        events::check(
+            "mode change",
+            self.set_mode(OperatingMode::GrayscalePWM),
+        )?;
+        events::frame("grayscale", packed);
+        if self.update_strategy == UpdateStrategy::LatchAtBlank {
+            events::check("grayscale write", self.connector.shift(packed))?;
+            // A connector that can latch at the boundary by itself
+            // doesn't need on_blank_interrupt
+            self.latch_pending = !self.connector.latch_at_blank()?;
+            if !self.latch_pending {
+                self.finish_grayscale_latch()?;
+            }
+            return Ok(());
+        }
+        // Shifting overwrites any frame still waiting to be latched
+        self.latch_pending = false;
+        events::check(
+            "grayscale write",
+            self.connector
+                .shift(packed)
+                .and_then(|()| self.connector.latch()),
+        )?;
+        self.finish_grayscale_latch()
     }
-}
 
-#[cfg(test)]
-mod tests {
-    #[test]
-    fn it_works() {
-        assert_eq!(2 + 2, 4);
+    ///
+    /// Packs and latches a full set of dot correction values, bypassing
+    /// the values stored in the driver. The next grayscale frame is
+    /// followed by the extra SCLK pulse the chip needs after dot
+    /// correction, if the connector can send it (see
+    /// `Connector::pulse_sclk`).
+    ///
+    /// # Arguments
+    ///
+    /// * `values` - one value per channel, starting at channel 0. Only
+    ///   the lower 6 bits of each value are used.
+    ///
+    /// # Errors
+    ///
+    /// * `Error::OutOfRange` if `values` does not hold exactly `CHANNELS`
+    ///   values
+    /// * `Error::Pin` if the VPRG pin could not be driven (e.g. it is
+    ///   `Unconnected`)
+    /// * `Error::Unsupported` if the connector leaves XLAT transparent
+    ///   between transfers, see `Connector::latch_idles_low`
+    ///
+    pub fn write_dc_raw(&mut self, values: &[u8]) -> Result<()> {
+        if values.len() != CHANNELS {
+            return Err(Error::OutOfRange);
+        }
+
+        // One byte per channel is enough for up to 8 bits of dot
+        // correction
+        let mut packed = [0_u8; CHANNELS];
+        let packed = &mut packed[..Self::frame_len(CHIP::DC_FRAME_LEN)];
+        pack_dot_correction::<CHIP>(&mut BitWriter::new(packed), values)?;
+
+        events::check(
+            "mode change",
+            self.set_mode(OperatingMode::DotCorrection),
+        )?;
+        // Whatever is on the chip now didn't come from the stored values
+        self.dc_pushed = false;
+        // Shifting overwrites any grayscale frame waiting to be latched
+        self.latch_pending = false;
+        events::frame("dot correction", packed);
+        events::check(
+            "dot correction write",
+            self.connector
+                .shift(packed)
+                .and_then(|()| self.connector.latch()),
+        )
+    }
+
+    // Drive VPRG to select where the next latch puts the shifted data
+    fn set_mode(&mut self, mode: OperatingMode) -> Result<()> {
+        if mode == self.mode {
+            return Ok(());
+        }
+        // With XLAT transparent, changing VPRG copies whatever is in the
+        // shift register into the other register
+        if CHIP::MODE_SWITCH != ModeSwitch::VprgPin
+            || !self.connector.latch_idles_low()
+        {
+            return Err(Error::Unsupported);
+        }
+        // A frame still on its way would end up in the other register
+        self.connector.wait_idle()?;
+
+        match mode {
+            OperatingMode::GrayscalePWM => {
+                self.vprg_pin.set_low().map_err(|_| Error::Pin)?;
+                self.extra_sclk_pending =
+                    self.mode == OperatingMode::DotCorrection;
+            }
+            OperatingMode::DotCorrection => {
+                self.vprg_pin.set_high().map_err(|_| Error::Pin)?
+            }
+            OperatingMode::Eeprom if !CHIP::HAS_EEPROM => {
+                return Err(Error::Unsupported)
+            }
+            // Programming the EEPROM needs 22V on VPRG, which can't be
+            // done from a GPIO
+            OperatingMode::Eeprom => return Err(Error::NotConnected),
+        }
+        events::mode_change(self.mode, mode);
+        self.mode = mode;
+        Ok(())
+    }
+
+    // The first grayscale latch after dot correction data needs one
+    // more SCLK pulse once XLAT has gone low again
+    fn finish_grayscale_latch(&mut self) -> Result<()> {
+        if self.extra_sclk_pending {
+            events::check("extra SCLK", self.connector.pulse_sclk())?;
+            self.extra_sclk_pending = false;
+        }
+        Ok(())
+    }
+
+    // The stored levels as they should be sent, after any zone settings
+    fn frame_levels(&self) -> [u16; CHANNELS] {
+        #[cfg_attr(not(feature = "effects"), allow(unused_mut))]
+        let mut levels = self.levels.values;
+        #[cfg(feature = "effects")]
+        self.zones.apply(&mut levels);
+        levels
+    }
+
+    // Length of a frame for all chained chips, given the length for one
+    fn frame_len(chip_frame_len: usize) -> usize {
+        chip_frame_len * (CHANNELS / CHIP::CHANNELS)
+    }
+
+    ///
+    /// Construct a new TLC5940 driver instance from any `Connector`,
+    /// e.g. one written for a custom transport.
+    ///
+    /// # Arguments
+    ///
+    /// * `connector` - shifts and latches data into the chip
+    /// * `vprg_pin` - the VPRG PIN used to select dot correction mode, or `Unconnected` if VPRG is tied to GND
+    ///
+    /// # Errors
+    ///
+    /// * `Error::OutOfRange` - `CHANNELS` isn't a multiple of the chip's
+    ///   channel count, is over 256, or doesn't match the number of chips a
+    ///   `ParallelLatchConnector` has XLAT pins for
+    /// * `Error::Unsupported` - the chip's frames need more than bare
+    ///   levels, see `ChipVariant`
+    ///
+    pub fn from_connector(
+        connector: CONNECTOR,
+        vprg_pin: VPRG,
+        blank_pin: BLANK,
+        xerr_pin: XERR,
+    ) -> Result<Self> {
+        TLC594x::new(connector, vprg_pin, blank_pin, xerr_pin)
+    }
+
+    // internal constructor, users should call ::from_pins or ::from_spi
+    fn new(
+        connector: CONNECTOR,
+        vprg_pin: VPRG,
+        blank_pin: BLANK,
+        xerr_pin: XERR,
+    ) -> Result<Self> {
+        // Outputs are numbered with a u8, so at most 256 can be driven
+        if CHANNELS == 0 || CHANNELS % CHIP::CHANNELS != 0 || CHANNELS > 256 {
+            return Err(Error::OutOfRange);
+        }
+        // Frames are packed as bare levels, and the register is chosen
+        // with VPRG
+        if CHIP::GS_FRAME_LEN * 8 != CHIP::CHANNELS * CHIP::GS_BITS as usize
+            || CHIP::MODE_SWITCH == ModeSwitch::SelectBit
+        {
+            return Err(Error::Unsupported);
+        }
+        // A connector that addresses each chip separately has to cover
+        // exactly the chips the levels are for
+        if let Some(devices) = connector.devices() {
+            if CHANNELS != devices * CHIP::CHANNELS {
+                return Err(Error::OutOfRange);
+            }
+        }
+
+        let mut tlc5940 = Self {
+            connector,
+            chip: PhantomData,
+            vprg_pin,
+            // VPRG is assumed to come up LOW; an unconnected VPRG should
+            // be tied to GND
+            mode: OperatingMode::GrayscalePWM,
+            blank_pin,
+            blanked: false,
+            xerr_pin,
+            // Matches the factory EEPROM contents
+            dot_correction: [0x3f; CHANNELS],
+            dc_pushed: false,
+            update_strategy: UpdateStrategy::default(),
+            latch_pending: false,
+            extra_sclk_pending: false,
+            #[cfg(feature = "effects")]
+            zones: Zones::default(),
+            #[cfg(feature = "diag")]
+            xerr_filter: XerrFilter::default(),
+            #[cfg(feature = "diag")]
+            labels: [None; CHANNELS],
+            levels: Levels::new(),
+        };
+
+        tlc5940.init()?;
+        Ok(tlc5940)
+    }
+
+    fn init(&mut self) -> Result<()> {
+        // Probably don't need this function
+        //self.blank(false);
+
+        // Without EEPROM the dot correction register holds no useful
+        // values until it has been written
+        if !CHIP::HAS_EEPROM && CHIP::DC_BITS > 0 {
+            self.set_dot_correction()?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<CONNECTOR, VPRG, BLANK, XERR, CHIP, const CHANNELS: usize> fmt::Debug
+    for TLC594x<CONNECTOR, VPRG, BLANK, XERR, CHIP, CHANNELS>
+where
+    CONNECTOR: fmt::Debug,
+    VPRG: OutputPin,
+    BLANK: OutputPin,
+    XERR: InputPin,
+    CHIP: ChipVariant,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TLC594x")
+            .field("connector", &self.connector)
+            .field("chip", &core::any::type_name::<CHIP>())
+            .field("mode", &self.mode)
+            .field("blanked", &self.blanked)
+            .field("dc_pushed", &self.dc_pushed)
+            .field("update_strategy", &self.update_strategy)
+            .field("latch_pending", &self.latch_pending)
+            .field("levels", &LevelSummary(&self.levels.values))
+            .finish()
+    }
+}
+
+// Only the first few levels are sent to keep the message small enough
+// for RTT
+#[cfg(feature = "defmt")]
+impl<CONNECTOR, VPRG, BLANK, XERR, CHIP, const CHANNELS: usize> defmt::Format
+    for TLC594x<CONNECTOR, VPRG, BLANK, XERR, CHIP, CHANNELS>
+where
+    VPRG: OutputPin,
+    BLANK: OutputPin,
+    XERR: InputPin,
+    CHIP: ChipVariant,
+{
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(
+            f,
+            "TLC594x {{ mode: {}, blanked: {=bool}, dc_pushed: {=bool}, \
+             chips: {=usize}, levels: {}.. }}",
+            self.mode,
+            self.blanked,
+            self.dc_pushed,
+            CHANNELS / CHIP::CHANNELS,
+            &self.levels.values[..CHANNELS.min(4)],
+        )
+    }
+}
+
+impl<DATA, XLAT, SCK, VPRG, BLANK, XERR, CHIP, const CHANNELS: usize>
+    TLC594x<PinConnector<DATA, XLAT, SCK>, VPRG, BLANK, XERR, CHIP, CHANNELS>
+where
+    DATA: OutputPin,
+    XLAT: OutputPin,
+    SCK: OutputPin,
+    VPRG: OutputPin,
+    BLANK: OutputPin,
+    XERR: InputPin,
+    CHIP: ChipVariant,
+{
+    ///
+    /// Construct a new TLC5940 driver instance from DATA, XLAT and SCK pins.
+    ///
+    /// # Arguments
+    ///
+    /// * `displays` - number of displays connected in series
+    /// * `data` - the MOSI/DATA PIN used to send data through to the display set to output mode
+    /// * `xlat` - the XLAT PIN used to latch the shifted data, set to output mode
+    /// * `sck` - the SCK clock PIN used to drive the clock set to output mode
+    /// * `vprg_pin` - the VPRG PIN used to select dot correction mode, or `Unconnected` if VPRG is tied to GND
+    ///
+    /// # Errors
+    ///
+    /// * `DataError` - returned in case there was an error during data transfer
+    ///
+    pub fn from_pins(
+        data: DATA,
+        xlat: XLAT,
+        sck: SCK,
+        vprg_pin: VPRG,
+        blank_pin: BLANK,
+        xerr_pin: XERR,
+    ) -> Result<Self> {
+        TLC594x::new(
+            PinConnector::new(data, xlat, sck),
+            vprg_pin,
+            blank_pin,
+            xerr_pin,
+        )
+    }
+}
+
+impl<DATA, XLAT, SCK, SOUT, VPRG, BLANK, XERR, CHIP, const CHANNELS: usize>
+    TLC594x<
+        PinConnector<DATA, XLAT, SCK, SOUT>,
+        VPRG,
+        BLANK,
+        XERR,
+        CHIP,
+        CHANNELS,
+    >
+where
+    DATA: OutputPin,
+    XLAT: OutputPin,
+    SCK: OutputPin,
+    SOUT: InputPin,
+    VPRG: OutputPin,
+    BLANK: OutputPin,
+    XERR: InputPin,
+    CHIP: ChipVariant,
+{
+    ///
+    /// Construct a new TLC5940 driver instance from DATA, XLAT and SCK
+    /// pins, along with the SOUT pin so that data can be read back from
+    /// the device.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - the MOSI/DATA PIN used to send data through to the display set to output mode
+    /// * `xlat` - the XLAT PIN used to latch the shifted data, set to output mode
+    /// * `sck` - the SCK clock PIN used to drive the clock set to output mode
+    /// * `sout` - the SOUT PIN set to input mode
+    /// * `vprg_pin` - the VPRG PIN used to select dot correction mode, or `Unconnected` if VPRG is tied to GND
+    ///
+    /// # Errors
+    ///
+    /// * `DataError` - returned in case there was an error during data transfer
+    ///
+    pub fn from_pins_with_sout(
+        data: DATA,
+        xlat: XLAT,
+        sck: SCK,
+        sout: SOUT,
+        vprg_pin: VPRG,
+        blank_pin: BLANK,
+        xerr_pin: XERR,
+    ) -> Result<Self> {
+        TLC594x::new(
+            PinConnector::with_sout(data, xlat, sck, sout),
+            vprg_pin,
+            blank_pin,
+            xerr_pin,
+        )
+    }
+}
+
+impl<SPI, VPRG, BLANK, XERR, CHIP, const CHANNELS: usize>
+    TLC594x<SpiConnector<SPI>, VPRG, BLANK, XERR, CHIP, CHANNELS>
+where
+    SPI: Write<u8>,
+    VPRG: OutputPin,
+    BLANK: OutputPin,
+    XERR: InputPin,
+    CHIP: ChipVariant,
+{
+    ///
+    /// Construct a new MAX7219 driver instance from pre-existing SPI in full hardware mode.
+    /// The SPI will control the CS line, which should be wired to XLAT, according to it's internal mode set.
+    /// If you need XLAT to be controlled manually use TLC5940::from_spi_cs
+    ///
+    /// * `NOTE` - make sure the SPI is initialized in MODE_0 with max 10 Mhz frequency.
+    ///
+    /// CS idles high, so XLAT is transparent between transfers and VPRG
+    /// can't be switched without corrupting one of the registers. Dot
+    /// correction writes return `Error::Unsupported`, and VPRG should be
+    /// tied to GND. Chips that need dot correction written at start-up,
+    /// such as the TLC5941, can't be driven this way.
+    ///
+    /// # Arguments
+    ///
+    /// * `displays` - number of displays connected in series
+    /// * `spi` - the SPI interface initialized with MOSI, MISO(unused) and CLK
+    /// * `vprg_pin` - the VPRG PIN used to select dot correction mode, or `Unconnected` if VPRG is tied to GND
+    ///
+    /// # Errors
+    ///
+    /// * `DataError` - returned in case there was an error during data transfer
+    /// * `Error::OutOfRange` - `displays` isn't the number of chips
+    ///   `CHANNELS` covers
+    /// * `Error::Unsupported` - the chip needs dot correction written
+    ///   before it can be used
+    ///
+    pub fn from_spi(
+        displays: usize,
+        spi: SPI,
+        vprg_pin: VPRG,
+        blank_pin: BLANK,
+        xerr_pin: XERR,
+    ) -> Result<Self> {
+        if displays != CHANNELS / CHIP::CHANNELS {
+            return Err(Error::OutOfRange);
+        }
+        TLC594x::new(
+            SpiConnector::new(displays, spi),
+            vprg_pin,
+            blank_pin,
+            xerr_pin,
+        )
+    }
+}
+
+impl<SPI, XLAT, VPRG, BLANK, XERR, CHIP, const CHANNELS: usize>
+    TLC594x<SpiConnectorSW<SPI, XLAT>, VPRG, BLANK, XERR, CHIP, CHANNELS>
+where
+    SPI: Write<u8>,
+    XLAT: OutputPin,
+    VPRG: OutputPin,
+    BLANK: OutputPin,
+    XERR: InputPin,
+    CHIP: ChipVariant,
+{
+    ///
+    /// Construct a new TLC5940 driver instance from pre-existing SPI and XLAT pin
+    /// set to output. This version of the connection drives XLAT manually so
+    /// that the driver controls when the shifted data is latched.
+    ///
+    /// * `NOTE` - make sure the SPI is initialized in MODE_0 with max 10 Mhz frequency.
+    ///
+    /// # Arguments
+    ///
+    /// * `displays` - number of displays connected in series
+    /// * `spi` - the SPI interface initialized with MOSI, MISO(unused) and CLK
+    /// * `xlat` - the XLAT PIN used to latch the shifted data, set to output mode
+    /// * `vprg_pin` - the VPRG PIN used to select dot correction mode, or `Unconnected` if VPRG is tied to GND
+    ///
+    /// # Errors
+    ///
+    /// * `DataError` - returned in case there was an error during data transfer
+    /// * `Error::OutOfRange` - `displays` isn't the number of chips
+    ///   `CHANNELS` covers
+    ///
+    pub fn from_spi_cs(
+        displays: usize,
+        spi: SPI,
+        xlat: XLAT,
+        vprg_pin: VPRG,
+        blank_pin: BLANK,
+        xerr_pin: XERR,
+    ) -> Result<Self> {
+        if displays != CHANNELS / CHIP::CHANNELS {
+            return Err(Error::OutOfRange);
+        }
+        TLC594x::new(
+            SpiConnectorSW::new(displays, spi, xlat),
+            vprg_pin,
+            blank_pin,
+            xerr_pin,
+        )
+    }
+}
+
+impl<SPI, XLAT, VPRG, BLANK, XERR, CHIP, const CHANNELS: usize>
+    TLC594x<SpiTransferConnector<SPI, XLAT>, VPRG, BLANK, XERR, CHIP, CHANNELS>
+where
+    SPI: Write<u8> + Transfer<u8>,
+    XLAT: OutputPin,
+    VPRG: OutputPin,
+    BLANK: OutputPin,
+    XERR: InputPin,
+    CHIP: ChipVariant,
+{
+    ///
+    /// Construct a new TLC5940 driver instance from pre-existing SPI and
+    /// XLAT pin, like `from_spi_cs`, with MISO wired to SOUT so that data
+    /// can be read back from the device.
+    ///
+    /// * `NOTE` - make sure the SPI is initialized in MODE_0 with max 10 Mhz frequency.
+    ///
+    /// # Arguments
+    ///
+    /// * `spi` - the SPI interface initialized with MOSI, MISO and CLK
+    /// * `xlat` - the XLAT PIN used to latch the shifted data, set to output mode
+    /// * `vprg_pin` - the VPRG PIN used to select dot correction mode, or `Unconnected` if VPRG is tied to GND
+    ///
+    /// # Errors
+    ///
+    /// * `DataError` - returned in case there was an error during data transfer
+    ///
+    pub fn from_spi_transfer(
+        spi: SPI,
+        xlat: XLAT,
+        vprg_pin: VPRG,
+        blank_pin: BLANK,
+        xerr_pin: XERR,
+    ) -> Result<Self> {
+        TLC594x::new(
+            SpiTransferConnector::new(spi, xlat),
+            vprg_pin,
+            blank_pin,
+            xerr_pin,
+        )
+    }
+}
+
+impl<I2C, XLAT, VPRG, BLANK, XERR, CHIP, const CHANNELS: usize>
+    TLC594x<Sc18is602Connector<I2C, XLAT>, VPRG, BLANK, XERR, CHIP, CHANNELS>
+where
+    I2C: embedded_hal::blocking::i2c::Write,
+    XLAT: OutputPin,
+    VPRG: OutputPin,
+    BLANK: OutputPin,
+    XERR: InputPin,
+    CHIP: ChipVariant,
+{
+    ///
+    /// Construct a new TLC5940 driver instance talking through an
+    /// SC18IS602/SC18IS606 I2C-to-SPI bridge, with XLAT driven from a
+    /// GPIO. The bridge's SPI interface is configured before anything
+    /// is sent.
+    ///
+    /// # Arguments
+    ///
+    /// * `i2c` - the I2C bus the bridge is on
+    /// * `address` - the bridge's 7-bit address, 0x28-0x2f depending on
+    ///   how A0-A2 are strapped
+    /// * `xlat` - the XLAT PIN used to latch the shifted data, set to output mode
+    /// * `vprg_pin` - the VPRG PIN used to select dot correction mode, or `Unconnected` if VPRG is tied to GND
+    ///
+    /// # Errors
+    ///
+    /// * `Error::Spi` - the bridge could not be configured
+    ///
+    pub fn from_sc18is602(
+        i2c: I2C,
+        address: u8,
+        xlat: XLAT,
+        vprg_pin: VPRG,
+        blank_pin: BLANK,
+        xerr_pin: XERR,
+    ) -> Result<Self> {
+        let mut connector = Sc18is602Connector::new(i2c, address, xlat);
+        connector.configure()?;
+        TLC594x::new(connector, vprg_pin, blank_pin, xerr_pin)
+    }
+}
+
+impl<
+        SPI,
+        XLAT,
+        VPRG,
+        BLANK,
+        XERR,
+        CHIP,
+        const DEVICES: usize,
+        const CHANNELS: usize,
+    >
+    TLC594x<
+        ParallelLatchConnector<SpiConnector<SPI>, XLAT, DEVICES>,
+        VPRG,
+        BLANK,
+        XERR,
+        CHIP,
+        CHANNELS,
+    >
+where
+    SPI: Write<u8>,
+    XLAT: OutputPin,
+    VPRG: OutputPin,
+    BLANK: OutputPin,
+    XERR: InputPin,
+    CHIP: ChipVariant,
+{
+    ///
+    /// Construct a new TLC5940 driver instance for several chips that
+    /// share SIN and SCLK on one SPI bus, each with its own XLAT pin.
+    /// VPRG, BLANK and XERR are shared. `CHANNELS` covers all the chips,
+    /// with chip 0 driving the first 16 outputs.
+    ///
+    /// # Arguments
+    ///
+    /// * `spi` - the SPI interface initialized with MOSI, MISO(unused) and CLK
+    /// * `xlats` - the XLAT PIN of each chip, set to output mode
+    /// * `vprg_pin` - the VPRG PIN used to select dot correction mode, or `Unconnected` if VPRG is tied to GND
+    ///
+    /// # Errors
+    ///
+    /// * `Error::OutOfRange` - `CHANNELS` doesn't match the number of
+    ///   chips
+    ///
+    pub fn from_spi_parallel(
+        spi: SPI,
+        xlats: [XLAT; DEVICES],
+        vprg_pin: VPRG,
+        blank_pin: BLANK,
+        xerr_pin: XERR,
+    ) -> Result<Self> {
+        TLC594x::new(
+            ParallelLatchConnector::new(SpiConnector::new(1, spi), xlats),
+            vprg_pin,
+            blank_pin,
+            xerr_pin,
+        )
+    }
+}
+
+impl<
+        SHIFT,
+        XLAT,
+        VPRG,
+        BLANK,
+        XERR,
+        CHIP,
+        const DEVICES: usize,
+        const CHANNELS: usize,
+    >
+    TLC594x<
+        ParallelLatchConnector<SHIFT, XLAT, DEVICES>,
+        VPRG,
+        BLANK,
+        XERR,
+        CHIP,
+        CHANNELS,
+    >
+where
+    SHIFT: Connector,
+    XLAT: OutputPin,
+    VPRG: OutputPin,
+    BLANK: OutputPin,
+    XERR: InputPin,
+    CHIP: ChipVariant,
+{
+    ///
+    /// Transfer the stored levels for one chip to that chip only,
+    /// leaving the others alone. The frame is always latched straight
+    /// away, whatever the update strategy.
+    ///
+    /// # Errors
+    ///
+    /// * `Error::OutOfRange` - there is no chip `device`
+    ///
+    pub fn update_device(&mut self, device: usize) -> Result<()> {
+        if device >= DEVICES {
+            return Err(Error::OutOfRange);
+        }
+        let levels = self.frame_levels();
+        let start = device * CHIP::CHANNELS;
+
+        let mut packed = [[0_u8; 2]; CHANNELS];
+        let packed = &mut packed.as_flattened_mut()[..CHIP::GS_FRAME_LEN];
+        pack_grayscale::<CHIP>(
+            &mut BitWriter::new(packed),
+            &levels[start..start + CHIP::CHANNELS],
+        )?;
+
+        events::check(
+            "mode change",
+            self.set_mode(OperatingMode::GrayscalePWM),
+        )?;
+        events::frame("grayscale", packed);
+        events::check(
+            "grayscale write",
+            self.connector.shift_device(device, packed),
+        )?;
+        self.finish_grayscale_latch()
+    }
+}
+
+impl<ENGINE, VPRG, BLANK, XERR, CHIP, const CHANNELS: usize>
+    TLC594x<EngineConnector<ENGINE>, VPRG, BLANK, XERR, CHIP, CHANNELS>
+where
+    ENGINE: ShiftEngine,
+    VPRG: OutputPin,
+    BLANK: OutputPin,
+    XERR: InputPin,
+    CHIP: ChipVariant,
+{
+    ///
+    /// Construct a new TLC5940 driver instance that hands packed frames
+    /// to a `ShiftEngine`, such as a PIO program, instead of shifting
+    /// them out itself.
+    ///
+    /// # Arguments
+    ///
+    /// * `engine` - the engine that shifts and latches frames
+    /// * `vprg_pin` - the VPRG PIN used to select dot correction mode, or `Unconnected` if VPRG is tied to GND
+    /// * `blank_pin` - the BLANK PIN, or `Unconnected` if the engine generates BLANK
+    ///
+    pub fn from_engine(
+        engine: ENGINE,
+        vprg_pin: VPRG,
+        blank_pin: BLANK,
+        xerr_pin: XERR,
+    ) -> Result<Self> {
+        TLC594x::new(
+            EngineConnector::new(engine),
+            vprg_pin,
+            blank_pin,
+            xerr_pin,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::*;
+    use core::fmt::Write as _;
+
+    fn driver() -> TLC5940<MockConnector, MockPin, Unconnected, Unconnected> {
+        TLC594x::from_connector(
+            MockConnector::default(),
+            MockPin::default(),
+            Unconnected,
+            Unconnected,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn it_works() {
+        assert_eq!(2 + 2, 4);
+    }
+
+    #[test]
+    fn grayscale_packing() {
+        let mut tlc = driver();
+        let mut levels = [0; 16];
+        levels[0] = 0xabc;
+        levels[1] = 0xf123;
+        levels[15] = 0x0fff;
+        tlc.write_grayscale_raw(&levels).unwrap();
+
+        let conn = &tlc.connector;
+        assert_eq!(conn.len, 24);
+        assert_eq!(conn.latches, 1);
+        // Channel 15 is shifted out first
+        assert_eq!(conn.last[0], 0xff);
+        assert_eq!(conn.last[1] & 0xf0, 0xf0);
+        // Channels 1 and 0 are the last three bytes, upper bits masked
+        assert_eq!(&conn.last[21..24], &[0x12, 0x3a, 0xbc]);
+        assert!(!tlc.vprg_pin.high);
+    }
+
+    #[test]
+    fn dc_packing() {
+        let mut tlc = driver();
+        let mut values = [0; 16];
+        values[0] = 0x3f;
+        values[15] = 0xff;
+        tlc.write_dc_raw(&values).unwrap();
+
+        let conn = &tlc.connector;
+        assert_eq!(conn.len, 12);
+        assert_eq!(conn.last[0], 0xfc);
+        assert_eq!(conn.last[11], 0x3f);
+        assert!(tlc.vprg_pin.high);
+
+        // Switching back to grayscale drops VPRG
+        tlc.update().unwrap();
+        assert!(!tlc.vprg_pin.high);
+    }
+
+    #[test]
+    fn extra_sclk_after_dc() {
+        let mut tlc = driver();
+        tlc.update().unwrap();
+        assert_eq!(tlc.connector.sclk_pulses, 0);
+
+        // Only the first grayscale latch after dot correction gets one
+        tlc.set_dot_correction().unwrap();
+        tlc.update().unwrap();
+        assert_eq!(tlc.connector.latches, 3);
+        assert_eq!(tlc.connector.sclk_pulses, 1);
+        tlc.update().unwrap();
+        assert_eq!(tlc.connector.sclk_pulses, 1);
+
+        // With a deferred latch it follows the latch, not the shift
+        tlc.set_dot_correction().unwrap();
+        tlc.set_update_strategy(UpdateStrategy::LatchAtBlank);
+        tlc.update().unwrap();
+        assert_eq!(tlc.connector.sclk_pulses, 1);
+        assert!(tlc.on_blank_interrupt().unwrap());
+        assert_eq!(tlc.connector.sclk_pulses, 2);
+    }
+
+    #[test]
+    fn dot_correction_state() {
+        let mut tlc = driver();
+        assert!(!tlc.dot_correction_pushed());
+
+        tlc.set_dot_correction_level(3, 0xff).unwrap();
+        assert_eq!(tlc.dot_correction(3).unwrap(), 0x3f);
+        assert_eq!(tlc.dot_correction_all()[3], 0x3f);
+        assert!(matches!(tlc.dot_correction(16), Err(Error::OutOfRange)));
+
+        tlc.set_dot_correction().unwrap();
+        assert!(tlc.dot_correction_pushed());
+
+        tlc.set_dot_correction_level(4, 1).unwrap();
+        assert!(!tlc.dot_correction_pushed());
+    }
+
+    #[test]
+    #[cfg(feature = "color")]
+    fn pixels() {
+        use pixel::{Gray8, Rgb8};
+
+        let mut tlc = driver();
+        let frame = [Rgb8 {
+            r: 0xff,
+            g: 0x80,
+            b: 0,
+        }; 5];
+        tlc.write_pixels(frame.iter().copied()).unwrap();
+        assert_eq!(&tlc.levels.values[..3], &[0xfff, 0x808, 0]);
+        assert_eq!(tlc.levels.values[15], 0);
+        assert_eq!(tlc.connector.len, 24);
+
+        assert!(matches!(
+            tlc.set_pixels([Gray8(1); 17].iter().copied()),
+            Err(Error::OutOfRange)
+        ));
+
+        // Pixels wired G, R, B starting at output 3
+        tlc.set_levels([0; 16]).unwrap();
+        tlc.set_pixel_layout(PixelLayout {
+            start: 3,
+            order: &[1, 0, 2],
+        });
+        tlc.set_pixels(frame[..1].iter().copied()).unwrap();
+        assert_eq!(&tlc.levels.values[2..7], &[0, 0x808, 0xfff, 0, 0]);
+
+        tlc.set_pixel_layout(PixelLayout {
+            start: 0,
+            order: &[0, 0, 2],
+        });
+        assert!(matches!(
+            tlc.set_pixels(frame[..1].iter().copied()),
+            Err(Error::OutOfRange)
+        ));
+    }
+
+    #[test]
+    fn tlc5941_loads_dot_correction() {
+        let tlc = TLC5941::new(
+            MockConnector::default(),
+            MockPin::default(),
+            Unconnected,
+            Unconnected,
+        )
+        .unwrap();
+        assert!(tlc.dot_correction_pushed());
+        assert_eq!(tlc.connector.len, 12);
+        assert_eq!(tlc.connector.last[0], 0xff);
+
+        // VPRG has to be wired up to load dot correction
+        assert!(TLC5941::new(
+            MockConnector::default(),
+            Unconnected,
+            Unconnected,
+            Unconnected,
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn eeprom_mode() {
+        let mut tlc = driver();
+        assert!(matches!(
+            tlc.set_mode(OperatingMode::Eeprom),
+            Err(Error::NotConnected)
+        ));
+
+        let mut tlc = TLC5941::new(
+            MockConnector::default(),
+            MockPin::default(),
+            Unconnected,
+            Unconnected,
+        )
+        .unwrap();
+        assert!(matches!(
+            tlc.set_mode(OperatingMode::Eeprom),
+            Err(Error::Unsupported)
+        ));
+    }
+
+    #[test]
+    fn tlc5947() {
+        // The TLC5947 has no dot correction, so none is written at start
+        // up
+        let mut tlc: TLC5947<_, _> = TLC594x::from_connector(
+            MockConnector::default(),
+            Unconnected,
+            MockPin::default(),
+            Unconnected,
+        )
+        .unwrap();
+        assert_eq!(tlc.connector.len, 0);
+        tlc.set_level(23, 0xfff).unwrap();
+        tlc.set_level(0, 0x123).unwrap();
+        assert!(matches!(tlc.set_level(24, 0), Err(Error::OutOfRange)));
+        tlc.update().unwrap();
+
+        let conn = &tlc.connector;
+        assert_eq!(conn.len, 36);
+        assert_eq!(&conn.last[..2], &[0xff, 0xf0]);
+        assert_eq!(&conn.last[34..36], &[0x01, 0x23]);
+
+        assert!(matches!(
+            tlc.write_grayscale_raw(&[0xfff; 48]),
+            Err(Error::OutOfRange)
+        ));
+        assert!(matches!(tlc.set_dot_correction(), Err(Error::Unsupported)));
+        tlc.blank(true).unwrap();
+        assert!(tlc.blank_pin.high);
+
+        // Two chained TLC5947s
+        let mut tlc: TLC594x<_, _, _, _, variant::Tlc5947, 48> =
+            TLC594x::from_connector(
+                MockConnector::default(),
+                Unconnected,
+                Unconnected,
+                Unconnected,
+            )
+            .unwrap();
+        tlc.set_level(47, 0xfff).unwrap();
+        tlc.update().unwrap();
+        assert_eq!(tlc.connector.len, 72);
+        assert_eq!(&tlc.connector.last[..2], &[0xff, 0xf0]);
+    }
+
+    #[test]
+    fn chained() {
+        let mut tlc: TLC594x<_, _, _, _, variant::Tlc5940, 32> = TLC594x::new(
+            MockConnector::default(),
+            MockPin::default(),
+            Unconnected,
+            Unconnected,
+        )
+        .unwrap();
+        tlc.set_level(31, 0xfff).unwrap();
+        tlc.set_level(0, 0xabc).unwrap();
+        assert!(matches!(tlc.set_level(32, 0), Err(Error::OutOfRange)));
+        tlc.update().unwrap();
+
+        let conn = &tlc.connector;
+        assert_eq!(conn.len, 48);
+        assert_eq!(&conn.last[..2], &[0xff, 0xf0]);
+        assert_eq!(&conn.last[46..48], &[0x0a, 0xbc]);
+
+        tlc.set_dot_correction().unwrap();
+        assert_eq!(tlc.connector.len, 24);
+
+        // Only whole chips can be chained
+        let res: Result<TLC594x<_, _, _, _, variant::Tlc5940, 20>> =
+            TLC594x::new(
+                MockConnector::default(),
+                MockPin::default(),
+                Unconnected,
+                Unconnected,
+            );
+        assert!(matches!(res, Err(Error::OutOfRange)));
+
+        // Chips with headers or select bits need their own drivers
+        let res: Result<TLC594x<_, _, _, _, variant::Tlc59711, 12>> =
+            TLC594x::from_connector(
+                MockConnector::default(),
+                Unconnected,
+                Unconnected,
+                Unconnected,
+            );
+        assert!(matches!(res, Err(Error::Unsupported)));
+        let res: Result<TLC594x<_, _, _, _, variant::Tlc5955, 48>> =
+            TLC594x::from_connector(
+                MockConnector::default(),
+                Unconnected,
+                Unconnected,
+                Unconnected,
+            );
+        assert!(matches!(res, Err(Error::Unsupported)));
+
+        // 256 outputs is as many as a u8 can number
+        let res: Result<TLC594x<_, _, _, _, variant::Tlc5940, 272>> =
+            TLC594x::new(
+                MockConnector::default(),
+                MockPin::default(),
+                Unconnected,
+                Unconnected,
+            );
+        assert!(matches!(res, Err(Error::OutOfRange)));
+    }
+
+    #[test]
+    fn debug_summary() {
+        let mut tlc = driver();
+        tlc.set_level(2, 100).unwrap();
+        tlc.set_level(5, 4000).unwrap();
+        let mut buf = MockWriter::default();
+        write!(buf, "{:?}", LevelSummary(&tlc.levels.values)).unwrap();
+        assert_eq!(buf.as_str(), "2 of 16 on, max 4000");
+    }
+
+    #[test]
+    fn blank_state() {
+        let mut tlc = driver();
+        assert!(!tlc.is_blanked());
+        // A failed write leaves the recorded state alone
+        assert!(tlc.blank(true).is_err());
+        assert!(!tlc.is_blanked());
+    }
+
+    #[test]
+    fn wiring_aliases() {
+        let tlc: Tlc5940Chain<MockSpi, MockPin, MockPin, 32> =
+            TLC594x::from_spi_cs(
+                2,
+                MockSpi::default(),
+                MockPin::default(),
+                Unconnected,
+                MockPin::default(),
+                Unconnected,
+            )
+            .unwrap();
+        assert_eq!(tlc.dot_correction_all().len(), 32);
+        let res: Result<Tlc5940Chain<MockSpi, MockPin, MockPin, 32>> =
+            TLC594x::from_spi_cs(
+                3,
+                MockSpi::default(),
+                MockPin::default(),
+                Unconnected,
+                MockPin::default(),
+                Unconnected,
+            );
+        assert!(matches!(res, Err(Error::OutOfRange)));
+
+        let _: Tlc5940HwSpi<MockSpi, MockPin, MockPin> = TLC594x::from_spi_cs(
+            1,
+            MockSpi::default(),
+            MockPin::default(),
+            Unconnected,
+            MockPin::default(),
+            Unconnected,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn latch_at_blank() {
+        let mut tlc = driver();
+        assert_eq!(tlc.update_strategy(), UpdateStrategy::Immediate);
+        tlc.set_update_strategy(UpdateStrategy::LatchAtBlank);
+        tlc.set_level(3, 0x123).unwrap();
+        tlc.update().unwrap();
+        assert_eq!(tlc.connector.len, 24);
+        assert_eq!(tlc.connector.latches, 0);
+        assert!(tlc.latch_pending());
+
+        assert!(tlc.on_blank_interrupt().unwrap());
+        assert_eq!(tlc.connector.latches, 1);
+        // Nothing new to latch on the next cycle
+        assert!(!tlc.on_blank_interrupt().unwrap());
+        assert_eq!(tlc.connector.latches, 1);
+    }
+
+    #[test]
+    fn strategy_switch_drops_staged_frame() {
+        let mut tlc = driver();
+        tlc.set_update_strategy(UpdateStrategy::LatchAtBlank);
+        tlc.update().unwrap();
+        assert!(tlc.latch_pending());
+
+        // The immediate frame replaces the staged one
+        tlc.set_update_strategy(UpdateStrategy::Immediate);
+        tlc.update().unwrap();
+        assert!(!tlc.latch_pending());
+        assert!(!tlc.on_blank_interrupt().unwrap());
+        assert_eq!(tlc.connector.latches, 1);
+
+        tlc.set_update_strategy(UpdateStrategy::LatchAtBlank);
+        tlc.update().unwrap();
+        tlc.set_update_strategy(UpdateStrategy::Immediate);
+        tlc.update_streamed().unwrap();
+        assert!(!tlc.latch_pending());
+        assert_eq!(tlc.connector.latches, 2);
+    }
+
+    #[test]
+    fn latch_at_blank_needs_deferred_latch() {
+        // Hardware CS latches every frame as soon as it is shifted
+        let mut tlc: TLC5940<_, _, _, _> = TLC594x::from_spi(
+            1,
+            MockSpi::default(),
+            Unconnected,
+            Unconnected,
+            Unconnected,
+        )
+        .unwrap();
+        tlc.set_update_strategy(UpdateStrategy::LatchAtBlank);
+        assert!(matches!(tlc.update(), Err(Error::Unsupported)));
+        assert!(!tlc.latch_pending());
+
+        tlc.set_update_strategy(UpdateStrategy::Immediate);
+        tlc.update().unwrap();
+    }
+
+    #[test]
+    fn hardware_cs_keeps_vprg() {
+        // XLAT on CS is transparent between transfers, so raising VPRG
+        // would copy the grayscale frame into dot correction
+        let mut tlc: TLC5940<_, _, _, _> = TLC594x::from_spi(
+            1,
+            MockSpi::default(),
+            MockPin::default(),
+            Unconnected,
+            Unconnected,
+        )
+        .unwrap();
+        tlc.update().unwrap();
+        assert!(matches!(tlc.set_dot_correction(), Err(Error::Unsupported)));
+        assert!(!tlc.vprg_pin.high);
+        assert!(!tlc.dot_correction_pushed());
+
+        let tlc: Result<TLC5941<_, _, _, _>> = TLC594x::from_spi(
+            1,
+            MockSpi::default(),
+            MockPin::default(),
+            Unconnected,
+            Unconnected,
+        );
+        assert!(matches!(tlc, Err(Error::Unsupported)));
+    }
+
+    #[test]
+    #[cfg(feature = "effects")]
+    fn zones() {
+        let mut tlc = driver();
+        let right = tlc
+            .add_zone(Zone {
+                name: "right",
+                channels: &[0, 15],
+            })
+            .unwrap();
+        assert_eq!(tlc.zone_id("right"), Some(right));
+        assert!(tlc
+            .add_zone(Zone {
+                name: "bad",
+                channels: &[16],
+            })
+            .is_err());
+
+        tlc.set_zone(right, 0xfff).unwrap();
+        tlc.update().unwrap();
+        assert_eq!(tlc.connector.last[0], 0xff);
+
+        // Disabling sends the zone as off but keeps its levels
+        tlc.enable_zone(right, false).unwrap();
+        tlc.update().unwrap();
+        assert_eq!(tlc.connector.last[0], 0x00);
+        tlc.set_level(15, 0xfff).unwrap();
+        tlc.update_streamed().unwrap();
+        assert_eq!(tlc.connector.last[0], 0x00);
+        assert_eq!(tlc.levels.values[15], 0xfff);
+
+        assert!(!tlc.fade_zone(right, 0, 0x800).unwrap());
+        assert!(tlc.fade_zone(right, 0, 0x800).unwrap());
+        assert_eq!(tlc.levels.values[0], 0);
+    }
+
+    #[test]
+    fn streamed_update() {
+        let mut tlc = driver();
+        tlc.set_level(0, 0xabc).unwrap();
+        tlc.set_level(7, 0x123).unwrap();
+        tlc.set_level(15, 0xfff).unwrap();
+        tlc.update().unwrap();
+        let packed = tlc.connector.last;
+
+        tlc.connector = MockConnector::default();
+        tlc.update_streamed().unwrap();
+        assert_eq!(tlc.connector.len, 24);
+        assert_eq!(tlc.connector.last, packed);
+        assert_eq!(tlc.connector.latches, 1);
+
+        tlc.set_update_strategy(UpdateStrategy::LatchAtBlank);
+        assert!(matches!(tlc.update_streamed(), Err(Error::Unsupported)));
+    }
+
+    #[test]
+    fn streamed_frame_matches_packed() {
+        fn check<CHIP: ChipVariant>(levels: &[u16], len: usize) {
+            let mut packed = [0_u8; 128];
+            pack_grayscale::<CHIP>(
+                &mut BitWriter::new(&mut packed[..len]),
+                levels,
+            )
+            .unwrap();
+            let streamed =
+                GrayscaleBytes::<CHIP, _>::new(levels.len(), len, |channel| {
+                    levels[channel]
+                })
+                .unwrap();
+            assert!(streamed.eq(packed[..len].iter().copied()));
+        }
+
+        let mut levels = [0_u16; 48];
+        for (idx, level) in levels.iter_mut().enumerate() {
+            *level = (idx as u16).wrapping_mul(0x1357);
+        }
+        // Three chained TLC5940s
+        check::<variant::Tlc5940>(&levels, 72);
+        // A TLC5955, whose frame ends in a padding byte
+        check::<variant::Tlc5955>(&levels, 97);
+        assert!(
+            GrayscaleBytes::<variant::Tlc5940, _>::new(20, 30, |_| 0).is_err()
+        );
+    }
+
+    #[test]
+    fn refresh_loop() {
+        let mut tlc: TLC5940<_, _, _, Unconnected> = TLC594x::new(
+            MockConnector::default(),
+            MockPin::default(),
+            MockPin::default(),
+            Unconnected,
+        )
+        .unwrap();
+        let mut gsclk = MockPin::default();
+        assert!(tlc.run_refresh_loop(&mut gsclk, 0, |_, _| Ok(())).is_err());
+
+        let res = tlc.run_refresh_loop(&mut gsclk, 2, |frame, levels| {
+            if frame == 3 {
+                return Err(Error::NotConnected);
+            }
+            levels[0] = frame as u16;
+            Ok(())
+        });
+        assert!(matches!(res, Err(Error::NotConnected)));
+        assert_eq!(tlc.connector.latches, 3);
+        assert_eq!(tlc.levels.values[0], 2);
+        // BLANK resets the grayscale counter at the start of every cycle
+        assert_eq!(tlc.blank_pin.pulses, 6);
+        assert_eq!(gsclk.pulses, 6 * 4096);
+        assert!(!tlc.blank_pin.high);
+
+        // Staged frames are latched at the start of their first cycle
+        tlc.set_update_strategy(UpdateStrategy::LatchAtBlank);
+        let res = tlc.run_refresh_loop(&mut gsclk, 1, |frame, _| {
+            if frame == 2 {
+                return Err(Error::NotConnected);
+            }
+            Ok(())
+        });
+        assert!(matches!(res, Err(Error::NotConnected)));
+        assert_eq!(tlc.connector.latches, 5);
+        assert!(!tlc.latch_pending());
+    }
+
+    #[test]
+    fn software_gsclk() {
+        let mut tlc: TLC5940<_, _, _, Unconnected> = TLC594x::new(
+            MockConnector::default(),
+            MockPin::default(),
+            MockPin::default(),
+            Unconnected,
+        )
+        .unwrap();
+        tlc.set_update_strategy(UpdateStrategy::LatchAtBlank);
+        tlc.update().unwrap();
+
+        let mut gsclk = MockPin::default();
+        tlc.run_frame(&mut gsclk).unwrap();
+        assert_eq!(gsclk.pulses, 4096);
+        assert_eq!(tlc.blank_pin.pulses, 1);
+        assert!(!tlc.blank_pin.high);
+        assert_eq!(tlc.connector.latches, 1);
+    }
+
+    #[test]
+    #[cfg(feature = "diag")]
+    fn error_classification() {
+        let blank = core::cell::Cell::new(false);
+        let classify = |led_open, thermal, settle_us| {
+            let xerr = MockXerr {
+                blank: &blank,
+                led_open,
+                thermal,
+            };
+            let mut tlc: TLC5940<_, _, _, _> = TLC594x::new(
+                MockConnector::default(),
+                MockPin::default(),
+                SharedPin(&blank),
+                xerr,
+            )
+            .unwrap();
+            let mut delay = MockDelay::default();
+            let cause = tlc.classify_error(&mut delay, 50).unwrap();
+            // BLANK is left as it was
+            assert!(!tlc.is_blanked());
+            // XERR is only read again once it has had time to settle
+            assert_eq!(delay.total_us, settle_us);
+            cause
+        };
+
+        assert_eq!(classify(false, false, 0), None);
+        assert_eq!(classify(true, false, 50), Some(ErrorCause::LedOpen));
+        assert_eq!(classify(true, true, 50), Some(ErrorCause::Thermal));
+    }
+
+    #[test]
+    #[cfg(feature = "diag")]
+    fn labels() {
+        let mut tlc = driver();
+        tlc.set_label(11, Some(Label::new("STATUS_RED"))).unwrap();
+        assert!(tlc.set_label(16, Some(Label::new("NOPE"))).is_err());
+        assert_eq!(tlc.label(11).map(|l| l.name), Some("STATUS_RED"));
+        assert_eq!(tlc.label(10), None);
+        tlc.set_level(11, 42).unwrap();
+        #[cfg(feature = "effects")]
+        tlc.add_zone(Zone {
+            name: "status",
+            channels: &[11],
+        })
+        .unwrap();
+
+        let mut buf = MockWriter::default();
+        tlc.dump_state(&mut buf).unwrap();
+        let dump = buf.as_str();
+        assert!(dump.starts_with("channel 0: 0\n"));
+        assert!(dump.contains("\nSTATUS_RED: 42\n"));
+        #[cfg(feature = "effects")]
+        assert!(dump.ends_with("zone status: enabled\n"));
+    }
+
+    #[test]
+    fn shift_engine() {
+        let mut tlc: TLC5940<_, _, _, _> = TLC594x::from_engine(
+            MockEngine::default(),
+            MockPin::default(),
+            Unconnected,
+            Unconnected,
+        )
+        .unwrap();
+        tlc.update().unwrap();
+        assert_eq!(tlc.connector.engine().latched_now, 1);
+
+        // The engine latches at the boundary itself, so nothing is left
+        // for on_blank_interrupt
+        tlc.set_update_strategy(UpdateStrategy::LatchAtBlank);
+        tlc.update().unwrap();
+        assert_eq!(tlc.connector.engine().frames, 2);
+        assert_eq!(tlc.connector.engine().latched_at_boundary, 1);
+        assert!(!tlc.latch_pending());
+
+        // Dot correction waits for the grayscale frame to be latched
+        // before VPRG is raised
+        tlc.connector.engine().boundary_polls.set(3);
+        tlc.set_dot_correction().unwrap();
+        assert_eq!(tlc.connector.engine().boundary_polls.get(), 0);
+        assert_eq!(tlc.connector.engine().frames, 3);
+    }
+
+    #[test]
+    fn parallel_latch() {
+        let connector = ParallelLatchConnector::new(
+            MockConnector::default(),
+            [MockPin::default(), MockPin::default()],
+        );
+        let mut tlc: TLC594x<_, _, _, _, variant::Tlc5940, 32> = TLC594x::new(
+            connector,
+            MockPin::default(),
+            Unconnected,
+            Unconnected,
+        )
+        .unwrap();
+
+        tlc.set_level(16, 0xfff).unwrap();
+        tlc.update_device(1).unwrap();
+        let shifter = tlc.connector.shifter_mut();
+        // Only chip 1's frame was shifted, with output 16 last
+        assert_eq!(shifter.len, 24);
+        assert_eq!(&shifter.last[22..24], &[0x0f, 0xff]);
+        assert!(tlc.update_device(2).is_err());
+
+        // Every chip is latched as its part of the frame is shifted
+        tlc.set_update_strategy(UpdateStrategy::LatchAtBlank);
+        assert!(matches!(tlc.update(), Err(Error::Unsupported)));
+        assert!(!tlc.latch_pending());
+
+        assert!(
+            TLC594x::<_, _, _, _, variant::Tlc5940, 48>::from_spi_parallel(
+                MockSpi::default(),
+                [MockPin::default(), MockPin::default()],
+                Unconnected,
+                Unconnected,
+                Unconnected,
+            )
+            .is_err()
+        );
+        // Checked however the connector was built
+        let connector = ParallelLatchConnector::new(
+            MockConnector::default(),
+            [(); 4].map(|()| MockPin::default()),
+        );
+        assert!(matches!(
+            TLC594x::<_, _, _, _, variant::Tlc5940, 32>::from_connector(
+                connector,
+                MockPin::default(),
+                Unconnected,
+                Unconnected,
+            ),
+            Err(Error::OutOfRange)
+        ));
+    }
+
+    #[test]
+    fn raw_length_checked() {
+        let mut tlc = driver();
+        assert!(matches!(
+            tlc.write_grayscale_raw(&[0; 15]),
+            Err(Error::OutOfRange)
+        ));
+        assert!(matches!(tlc.write_dc_raw(&[0; 17]), Err(Error::OutOfRange)));
     }
 }
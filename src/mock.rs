@@ -0,0 +1,313 @@
+use core::cell::Cell;
+
+#[cfg(feature = "diag")]
+use embedded_hal::blocking::delay::DelayUs;
+use embedded_hal::blocking::i2c;
+use embedded_hal::blocking::spi::{Transfer, Write};
+#[cfg(feature = "diag")]
+use embedded_hal::digital::v2::InputPin;
+use embedded_hal::digital::v2::OutputPin;
+use embedded_hal::PwmPin;
+
+use crate::connectors::Connector;
+use crate::engine::ShiftEngine;
+use crate::Result;
+
+/// Records the last frame shifted into it and counts latches and extra
+/// SCLK pulses
+#[derive(Debug)]
+pub(crate) struct MockConnector {
+    pub(crate) last: [u8; 128],
+    pub(crate) len: usize,
+    pub(crate) latches: usize,
+    pub(crate) sclk_pulses: usize,
+}
+
+impl Default for MockConnector {
+    fn default() -> Self {
+        MockConnector {
+            last: [0; 128],
+            len: 0,
+            latches: 0,
+            sclk_pulses: 0,
+        }
+    }
+}
+
+impl Connector for MockConnector {
+    fn shift(&mut self, data: &[u8]) -> Result<()> {
+        self.last[..data.len()].copy_from_slice(data);
+        self.len = data.len();
+        Ok(())
+    }
+
+    fn latch(&mut self) -> Result<()> {
+        self.latches += 1;
+        Ok(())
+    }
+
+    fn pulse_sclk(&mut self) -> Result<()> {
+        self.sclk_pulses += 1;
+        Ok(())
+    }
+}
+
+/// Output pin that remembers its state and counts rising edges
+#[derive(Default)]
+pub(crate) struct MockPin {
+    pub(crate) high: bool,
+    pub(crate) pulses: usize,
+}
+
+impl OutputPin for MockPin {
+    type Error = ();
+    fn set_low(&mut self) -> core::result::Result<(), ()> {
+        self.high = false;
+        Ok(())
+    }
+    fn set_high(&mut self) -> core::result::Result<(), ()> {
+        if !self.high {
+            self.pulses += 1;
+        }
+        self.high = true;
+        Ok(())
+    }
+}
+
+/// SPI bus that records everything written to it
+pub(crate) struct MockSpi {
+    pub(crate) written: [u8; 256],
+    pub(crate) len: usize,
+}
+
+impl Default for MockSpi {
+    fn default() -> Self {
+        MockSpi {
+            written: [0; 256],
+            len: 0,
+        }
+    }
+}
+
+impl Write<u8> for MockSpi {
+    type Error = ();
+    fn write(&mut self, words: &[u8]) -> core::result::Result<(), ()> {
+        self.written[self.len..self.len + words.len()].copy_from_slice(words);
+        self.len += words.len();
+        Ok(())
+    }
+}
+
+impl Transfer<u8> for MockSpi {
+    type Error = ();
+    fn transfer<'w>(
+        &mut self,
+        words: &'w mut [u8],
+    ) -> core::result::Result<&'w [u8], ()> {
+        self.write(words)?;
+        for word in words.iter_mut() {
+            *word = !*word;
+        }
+        Ok(words)
+    }
+}
+
+/// Timer PWM channel that remembers its duty and whether it is running
+#[derive(Default)]
+pub(crate) struct MockPwm {
+    pub(crate) duty: u32,
+    pub(crate) enabled: bool,
+}
+
+impl PwmPin for MockPwm {
+    type Duty = u32;
+    fn disable(&mut self) {
+        self.enabled = false;
+    }
+    fn enable(&mut self) {
+        self.enabled = true;
+    }
+    fn get_duty(&self) -> u32 {
+        self.duty
+    }
+    fn get_max_duty(&self) -> u32 {
+        8192
+    }
+    fn set_duty(&mut self, duty: u32) {
+        self.duty = duty;
+    }
+}
+
+/// Delay that adds up how long it was asked to wait
+#[cfg(feature = "diag")]
+#[derive(Default)]
+pub(crate) struct MockDelay {
+    pub(crate) total_us: u32,
+}
+
+#[cfg(feature = "diag")]
+impl DelayUs<u32> for MockDelay {
+    fn delay_us(&mut self, us: u32) {
+        self.total_us += us;
+    }
+}
+
+/// Output pin whose state can be seen by other mocks
+#[cfg(feature = "diag")]
+pub(crate) struct SharedPin<'a>(pub(crate) &'a Cell<bool>);
+
+#[cfg(feature = "diag")]
+impl OutputPin for SharedPin<'_> {
+    type Error = ();
+    fn set_low(&mut self) -> core::result::Result<(), ()> {
+        self.0.set(false);
+        Ok(())
+    }
+    fn set_high(&mut self) -> core::result::Result<(), ()> {
+        self.0.set(true);
+        Ok(())
+    }
+}
+
+/// XERR line for a chip with an open LED and/or a thermal error. LED
+/// Open Detection is only reported while `blank` is LOW.
+#[cfg(feature = "diag")]
+pub(crate) struct MockXerr<'a> {
+    pub(crate) blank: &'a Cell<bool>,
+    pub(crate) led_open: bool,
+    pub(crate) thermal: bool,
+}
+
+#[cfg(feature = "diag")]
+impl InputPin for MockXerr<'_> {
+    type Error = ();
+    fn is_high(&self) -> core::result::Result<bool, ()> {
+        self.is_low().map(|low| !low)
+    }
+    fn is_low(&self) -> core::result::Result<bool, ()> {
+        Ok(self.thermal || (self.led_open && !self.blank.get()))
+    }
+}
+
+/// Collects formatted output so it can be checked
+pub(crate) struct MockWriter {
+    buf: [u8; 512],
+    len: usize,
+}
+
+impl MockWriter {
+    pub(crate) fn as_str(&self) -> &str {
+        core::str::from_utf8(&self.buf[..self.len]).unwrap()
+    }
+}
+
+impl Default for MockWriter {
+    fn default() -> Self {
+        MockWriter {
+            buf: [0; 512],
+            len: 0,
+        }
+    }
+}
+
+impl core::fmt::Write for MockWriter {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        self.buf[self.len..self.len + s.len()].copy_from_slice(s.as_bytes());
+        self.len += s.len();
+        Ok(())
+    }
+}
+
+/// I2C bus that records every write acknowledged on it. Acts like an
+/// SC18IS602 bridge in that it NACKs the next `busy_polls` writes after
+/// each SPI transfer, while the bridge would be clocking it out.
+pub(crate) struct MockI2c {
+    pub(crate) address: u8,
+    pub(crate) written: [u8; 512],
+    pub(crate) len: usize,
+    pub(crate) writes: usize,
+    pub(crate) busy_polls: usize,
+    pub(crate) nacks: usize,
+    busy: usize,
+}
+
+impl Default for MockI2c {
+    fn default() -> Self {
+        MockI2c {
+            address: 0,
+            written: [0; 512],
+            len: 0,
+            writes: 0,
+            busy_polls: 0,
+            nacks: 0,
+            busy: 0,
+        }
+    }
+}
+
+impl i2c::Write for MockI2c {
+    type Error = ();
+    fn write(
+        &mut self,
+        address: u8,
+        bytes: &[u8],
+    ) -> core::result::Result<(), ()> {
+        if self.busy > 0 {
+            self.busy -= 1;
+            self.nacks += 1;
+            return Err(());
+        }
+        self.address = address;
+        self.written[self.len..self.len + bytes.len()].copy_from_slice(bytes);
+        self.len += bytes.len();
+        self.writes += 1;
+        if bytes.first() == Some(&0x01) {
+            self.busy = self.busy_polls;
+        }
+        Ok(())
+    }
+}
+
+/// Shift engine that counts frames and latches. It reports itself busy
+/// for the next `busy_polls` calls to `busy`, and notes whether a frame
+/// was handed over before then. `boundary_polls` does the same for
+/// `latch_pending`.
+#[derive(Default)]
+pub(crate) struct MockEngine {
+    pub(crate) frames: usize,
+    pub(crate) latched_now: usize,
+    pub(crate) latched_at_boundary: usize,
+    pub(crate) busy_polls: Cell<usize>,
+    pub(crate) boundary_polls: Cell<usize>,
+    pub(crate) shifted_while_busy: bool,
+}
+
+impl ShiftEngine for MockEngine {
+    fn shift_frame(&mut self, _frame: &[u8]) -> Result<()> {
+        self.frames += 1;
+        self.shifted_while_busy |= self.busy_polls.get() > 0;
+        Ok(())
+    }
+
+    fn latch_now(&mut self) -> Result<()> {
+        self.latched_now += 1;
+        Ok(())
+    }
+
+    fn latch_at_boundary(&mut self) -> Result<()> {
+        self.latched_at_boundary += 1;
+        Ok(())
+    }
+
+    fn busy(&self) -> bool {
+        let polls = self.busy_polls.get();
+        self.busy_polls.set(polls.saturating_sub(1));
+        polls > 0
+    }
+
+    fn latch_pending(&self) -> bool {
+        let polls = self.boundary_polls.get();
+        self.boundary_polls.set(polls.saturating_sub(1));
+        polls > 0
+    }
+}
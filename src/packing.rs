@@ -0,0 +1,166 @@
+use core::marker::PhantomData;
+
+use crate::variant::ChipVariant;
+use crate::{Error, Result};
+
+/// Writes values of arbitrary bit width into a byte buffer, MSB first,
+/// in the order they should appear on the wire.
+pub(crate) struct BitWriter<'a> {
+    buf: &'a mut [u8],
+    pos: usize,
+}
+
+impl<'a> BitWriter<'a> {
+    /// Wraps `buf`, clearing any data already in it
+    pub(crate) fn new(buf: &'a mut [u8]) -> Self {
+        for byte in buf.iter_mut() {
+            *byte = 0;
+        }
+        BitWriter { buf, pos: 0 }
+    }
+
+    /// Appends the lower `bits` bits of `value`, MSB first. `bits` may
+    /// be more than 32, in which case `value` is zero-extended. Bits
+    /// that would run past the end of the buffer are dropped.
+    pub(crate) fn push(&mut self, value: u32, bits: u32) {
+        for i in (0..bits).rev() {
+            let byte = self.pos / 8;
+            if byte >= self.buf.len() {
+                return;
+            }
+            if value.checked_shr(i).unwrap_or(0) & 1 != 0 {
+                self.buf[byte] |= 0x80 >> (self.pos % 8);
+            }
+            self.pos += 1;
+        }
+    }
+}
+
+// Mask covering the lower `bits` bits
+fn mask(bits: u32) -> u32 {
+    1_u32.checked_shl(bits).map_or(u32::MAX, |bit| bit - 1)
+}
+
+/// Drops any bits of a grayscale level that the chip can't use
+pub(crate) fn clamp_level<CHIP: ChipVariant>(level: u16) -> u16 {
+    (level as u32 & mask(CHIP::GS_BITS)) as u16
+}
+
+/// Stores a dot correction value for `output`, dropping any bits the
+/// chip can't use
+pub(crate) fn store_dot_correction<CHIP: ChipVariant>(
+    values: &mut [u8],
+    output: u8,
+    value: u8,
+) -> Result<()> {
+    if CHIP::DC_BITS == 0 {
+        return Err(Error::Unsupported);
+    }
+    let slot = values.get_mut(output as usize).ok_or(Error::OutOfRange)?;
+    *slot = (value as u32 & mask(CHIP::DC_BITS)) as u8;
+    Ok(())
+}
+
+/// Appends one grayscale value per channel to `writer`, highest channel
+/// first. `levels` may cover several chained chips, in which case the
+/// furthest chip from the host is shifted first.
+pub(crate) fn pack_grayscale<CHIP: ChipVariant>(
+    writer: &mut BitWriter,
+    levels: &[u16],
+) -> Result<()> {
+    if levels.is_empty() || levels.len() % CHIP::CHANNELS != 0 {
+        return Err(Error::OutOfRange);
+    }
+    for level in levels.iter().rev() {
+        writer.push(*level as u32, CHIP::GS_BITS);
+    }
+    Ok(())
+}
+
+/// Produces the bytes of a grayscale frame one at a time, in the same
+/// order as `pack_grayscale`, asking `level` for each channel's level
+/// as it is needed. Used to stream frames that are too long to pack
+/// into a buffer first.
+pub(crate) struct GrayscaleBytes<CHIP, F> {
+    level: F,
+    // Channels not yet packed, which are taken from the top down
+    channels: usize,
+    // Bytes left in the frame, including any padding at the end
+    remaining: usize,
+    bits: u32,
+    pending: u32,
+    chip: PhantomData<CHIP>,
+}
+
+impl<CHIP, F> GrayscaleBytes<CHIP, F>
+where
+    CHIP: ChipVariant,
+    F: FnMut(usize) -> u16,
+{
+    /// A frame of `len` bytes for `channels` channels, which may cover
+    /// several chained chips
+    pub(crate) fn new(channels: usize, len: usize, level: F) -> Result<Self> {
+        if channels == 0 || channels % CHIP::CHANNELS != 0 {
+            return Err(Error::OutOfRange);
+        }
+        Ok(GrayscaleBytes {
+            level,
+            channels,
+            remaining: len,
+            bits: 0,
+            pending: 0,
+            chip: PhantomData,
+        })
+    }
+}
+
+impl<CHIP, F> Iterator for GrayscaleBytes<CHIP, F>
+where
+    CHIP: ChipVariant,
+    F: FnMut(usize) -> u16,
+{
+    type Item = u8;
+
+    fn next(&mut self) -> Option<u8> {
+        if self.remaining == 0 {
+            return None;
+        }
+        while self.bits < 8 && self.channels > 0 {
+            self.channels -= 1;
+            let level =
+                (self.level)(self.channels) as u32 & mask(CHIP::GS_BITS);
+            self.pending = (self.pending << CHIP::GS_BITS) | level;
+            self.bits += CHIP::GS_BITS;
+        }
+        // Once the levels run out the rest of the frame is zero padding
+        let byte = if self.bits >= 8 {
+            self.bits -= 8;
+            (self.pending >> self.bits) as u8
+        } else {
+            let byte = (self.pending << (8 - self.bits)) as u8;
+            self.bits = 0;
+            byte
+        };
+        self.pending &= mask(self.bits);
+        self.remaining -= 1;
+        Some(byte)
+    }
+}
+
+/// Appends one dot correction value per channel to `writer`, highest
+/// channel first. `values` may cover several chained chips.
+pub(crate) fn pack_dot_correction<CHIP: ChipVariant>(
+    writer: &mut BitWriter,
+    values: &[u8],
+) -> Result<()> {
+    if CHIP::DC_BITS == 0 {
+        return Err(Error::Unsupported);
+    }
+    if values.is_empty() || values.len() % CHIP::CHANNELS != 0 {
+        return Err(Error::OutOfRange);
+    }
+    for value in values.iter().rev() {
+        writer.push(*value as u32, CHIP::DC_BITS);
+    }
+    Ok(())
+}
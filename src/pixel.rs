@@ -0,0 +1,81 @@
+/// A pixel that occupies one or more consecutive output channels. Used
+/// to feed image or frame sources straight into the driver with
+/// `TLC5940::write_pixels`.
+pub trait Pixel {
+    /// Number of output channels taken up by each pixel
+    const CHANNELS: usize;
+
+    /// Whether `level` gives levels at the chip's own grayscale width,
+    /// to be stored as they are instead of being scaled from 12 bits
+    const NATIVE: bool = false;
+
+    /// Level of the pixel's `idx`th channel, scaled to the 12-bit
+    /// grayscale range unless the pixel is `NATIVE`
+    fn level(&self, idx: usize) -> u16;
+}
+
+///
+/// Where a sequence of pixels lands on the outputs. Pixels are laid out
+/// one after another from `start`, and `order` gives the position of
+/// each of a pixel's channels within its outputs, e.g. `[1, 0, 2]` for
+/// `Rgb8` pixels on LEDs wired G, R, B.
+///
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PixelLayout {
+    /// First output used by the first pixel
+    pub start: u8,
+    /// Position of each of the pixel's channels within its outputs.
+    /// Empty keeps the pixel's own order.
+    pub order: &'static [u8],
+}
+
+/// 8-bit single channel pixel
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Gray8(pub u8);
+
+/// 8-bit per channel RGB pixel, wired to three consecutive outputs in
+/// R, G, B order
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Rgb8 {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+// Stretch an 8-bit value over 12 bits so that 0xff maps to full scale
+fn scale_8_to_12(value: u8) -> u16 {
+    let value = value as u16;
+    (value << 4) | (value >> 4)
+}
+
+impl Pixel for Gray8 {
+    const CHANNELS: usize = 1;
+
+    fn level(&self, _idx: usize) -> u16 {
+        scale_8_to_12(self.0)
+    }
+}
+
+impl Pixel for Rgb8 {
+    const CHANNELS: usize = 3;
+
+    fn level(&self, idx: usize) -> u16 {
+        scale_8_to_12(match idx {
+            0 => self.r,
+            1 => self.g,
+            _ => self.b,
+        })
+    }
+}
+
+/// Raw levels at the chip's own grayscale width, 12 bits on the TLC5940
+/// and 16 on the TLC59711 and TLC5955, can be used as single channel
+/// pixels
+impl Pixel for u16 {
+    const CHANNELS: usize = 1;
+    const NATIVE: bool = true;
+
+    fn level(&self, _idx: usize) -> u16 {
+        *self
+    }
+}
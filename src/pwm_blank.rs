@@ -0,0 +1,108 @@
+use core::convert::Infallible;
+
+use embedded_hal::digital::v2::OutputPin;
+use embedded_hal::PwmPin;
+
+///
+/// Generates BLANK from a timer PWM output instead of a GPIO. The timer
+/// should be clocked in step with GSCLK and run at `blank_frequency_hz`
+/// so that BLANK pulses once at the end of every grayscale cycle; the
+/// chip then restarts its cycle without any help from software.
+///
+/// Can be passed anywhere a BLANK pin is expected. Setting it HIGH
+/// holds BLANK high to turn the outputs off, setting it LOW goes back
+/// to pulsing.
+///
+pub struct PwmBlank<PWM>
+where
+    PWM: PwmPin,
+{
+    pwm: PWM,
+    /// Duty cycle giving one BLANK pulse per grayscale cycle
+    pulse_duty: PWM::Duty,
+}
+
+impl<PWM> PwmBlank<PWM>
+where
+    PWM: PwmPin,
+    PWM::Duty: Copy,
+{
+    ///
+    /// Wraps a timer PWM channel and starts pulsing BLANK.
+    ///
+    /// # Arguments
+    ///
+    /// * `pwm` - the timer channel wired to BLANK, already set to run at
+    ///   `blank_frequency_hz`
+    /// * `pulse_duty` - the duty cycle of each pulse, see
+    ///   `blank_pulse_duty`
+    ///
+    pub fn new(mut pwm: PWM, pulse_duty: PWM::Duty) -> Self {
+        pwm.set_duty(pulse_duty);
+        pwm.enable();
+        PwmBlank { pwm, pulse_duty }
+    }
+
+    /// Gives the timer channel back, leaving it running
+    pub fn release(self) -> PWM {
+        self.pwm
+    }
+}
+
+impl<PWM> OutputPin for PwmBlank<PWM>
+where
+    PWM: PwmPin,
+    PWM::Duty: Copy,
+{
+    type Error = Infallible;
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.pwm.set_duty(self.pulse_duty);
+        Ok(())
+    }
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        let max = self.pwm.get_max_duty();
+        self.pwm.set_duty(max);
+        Ok(())
+    }
+}
+
+///
+/// Frequency the BLANK timer has to run at to pulse once per grayscale
+/// cycle, which lasts `2^gs_bits` GSCLK periods (4096 on the TLC5940).
+///
+pub fn blank_frequency_hz(gsclk_hz: u32, gs_bits: u32) -> u32 {
+    gsclk_hz.checked_shr(gs_bits).unwrap_or(0)
+}
+
+///
+/// Duty cycle for a BLANK pulse lasting about one GSCLK period, given
+/// the timer's max duty. Never less than 1 so that the pulse isn't lost
+/// on timers with a coarse duty resolution.
+///
+pub fn blank_pulse_duty(max_duty: u32, gs_bits: u32) -> u32 {
+    max_duty.checked_shr(gs_bits).unwrap_or(0).max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockPwm;
+
+    #[test]
+    fn blanking() {
+        let mut blank = PwmBlank::new(MockPwm::default(), 2);
+        assert!(blank.pwm.enabled);
+        assert_eq!(blank.pwm.duty, 2);
+        blank.set_high().unwrap();
+        assert_eq!(blank.pwm.duty, 8192);
+        blank.set_low().unwrap();
+        assert_eq!(blank.pwm.duty, 2);
+    }
+
+    #[test]
+    fn timing() {
+        assert_eq!(blank_frequency_hz(4_096_000, 12), 1000);
+        assert_eq!(blank_pulse_duty(8192, 12), 2);
+        assert_eq!(blank_pulse_duty(1000, 12), 1);
+    }
+}
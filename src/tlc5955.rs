@@ -0,0 +1,439 @@
+use core::fmt;
+
+use embedded_hal::blocking::spi::Write;
+use embedded_hal::digital::v2::OutputPin;
+
+use crate::connectors::*;
+use crate::debug::LevelSummary;
+use crate::events;
+use crate::levels::Levels;
+use crate::packing::*;
+use crate::variant::{ChipVariant, Tlc5955};
+use crate::{Error, Result};
+#[cfg(feature = "color")]
+use crate::{Pixel, PixelLayout};
+
+/// Number of padding bits at the start of each frame
+const PADDING_BITS: u32 = 7;
+/// Marks a frame as control data rather than grayscale data
+const CONTROL_COMMAND: u32 = 0x96;
+/// Unused bits between the function control bits and the command byte
+const CONTROL_UNUSED_BITS: u32 = 389;
+
+/// Function control bits stored in the control data latch
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct FunctionControl {
+    /// Repeat the PWM cycle automatically
+    pub dsprpt: bool,
+    /// Reset the grayscale counter whenever new data is latched
+    pub tmgrst: bool,
+    /// Copy the grayscale data to the PWM counter at the start of the
+    /// next cycle rather than on LAT
+    pub rfresh: bool,
+    /// Use enhanced spectrum PWM rather than conventional PWM
+    pub espwm: bool,
+    /// Raise the LED short detection voltage from 70% to 90% of VCC
+    pub lsdvlt: bool,
+}
+
+impl Default for FunctionControl {
+    fn default() -> Self {
+        FunctionControl {
+            dsprpt: true,
+            tmgrst: false,
+            rfresh: false,
+            espwm: false,
+            lsdvlt: false,
+        }
+    }
+}
+
+///
+/// Handles communication with the TLC5955 48-channel LED driver. Levels
+/// and dot correction use the same API as the TLC5940, while the extra
+/// settings held in the control data latch (max current, global
+/// brightness and function control) are stored in the driver and sent
+/// along with the dot correction values.
+///
+/// The connector's XLAT line drives LAT. GSCLK has to be supplied
+/// externally.
+///
+pub struct TLC5955<CONNECTOR> {
+    connector: CONNECTOR,
+
+    /// DOT correction values. Each channel should be in the 0-127 range.
+    dot_correction: [u8; Tlc5955::CHANNELS],
+    /// Whether the stored control data matches what was last latched
+    /// into the chip
+    dc_pushed: bool,
+    /// Maximum current setting for the red, green and blue output
+    /// groups. Each value should be in the 0-7 range.
+    max_current: [u8; 3],
+    /// Global brightness for the red, green and blue output groups.
+    /// Each value should be in the 0-127 range.
+    brightness: [u8; 3],
+    /// Function control bits
+    function_control: FunctionControl,
+    /// Brightness values for each channel, using the full 16-bit PWM
+    /// range. Channels are ordered R0, G0, B0, R1, ... B15.
+    levels: Levels<Tlc5955, { Tlc5955::CHANNELS }>,
+}
+
+impl<CONNECTOR: fmt::Debug> fmt::Debug for TLC5955<CONNECTOR> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TLC5955")
+            .field("connector", &self.connector)
+            .field("function_control", &self.function_control)
+            .field("max_current", &self.max_current)
+            .field("brightness", &self.brightness)
+            .field("dc_pushed", &self.dc_pushed)
+            .field("levels", &LevelSummary(&self.levels.values))
+            .finish()
+    }
+}
+
+impl<CONNECTOR> TLC5955<CONNECTOR>
+where
+    CONNECTOR: Connector,
+{
+    /// The connector frames are sent through, see
+    /// `TLC5940::connector_mut`
+    pub fn connector_mut(&mut self) -> &mut CONNECTOR {
+        &mut self.connector
+    }
+
+    /// Store the 16-bit level of one output, see `TLC5940::set_level`
+    pub fn set_level(&mut self, output: u8, level: u16) -> Result<()> {
+        self.levels.set(output, level)
+    }
+
+    /// Store the 16-bit levels of all 48 outputs at once
+    pub fn set_levels(
+        &mut self,
+        levels: [u16; Tlc5955::CHANNELS],
+    ) -> Result<()> {
+        self.levels.set_all(levels);
+        Ok(())
+    }
+
+    /// Lay pixels out as described for `TLC5940::set_pixel_layout`
+    #[cfg(feature = "color")]
+    pub fn set_pixel_layout(&mut self, layout: PixelLayout) {
+        self.levels.set_layout(layout);
+    }
+
+    ///
+    /// Store levels from a sequence of pixels, see `TLC5940::set_pixels`.
+    /// Pixel levels are scaled up from 12 to 16 bits, except for raw
+    /// `u16` pixels which are stored as they are.
+    ///
+    #[cfg(feature = "color")]
+    pub fn set_pixels<P, I>(&mut self, pixels: I) -> Result<()>
+    where
+        P: Pixel,
+        I: IntoIterator<Item = P>,
+    {
+        self.levels.set_pixels(pixels)
+    }
+
+    /// Store levels from a sequence of pixels and latch them, see `set_pixels`
+    #[cfg(feature = "color")]
+    pub fn write_pixels<P, I>(&mut self, pixels: I) -> Result<()>
+    where
+        P: Pixel,
+        I: IntoIterator<Item = P>,
+    {
+        self.set_pixels(pixels)?;
+        self.update()
+    }
+
+    /// Transfer the stored levels to the chip
+    pub fn update(&mut self) -> Result<()> {
+        let levels = self.levels.values;
+        self.write_grayscale_raw(&levels)
+    }
+
+    ///
+    /// Packs and latches a full set of grayscale values, bypassing the
+    /// levels stored in the driver.
+    ///
+    /// # Errors
+    ///
+    /// * `Error::OutOfRange` if `levels` does not hold exactly 48 values
+    ///
+    pub fn write_grayscale_raw(&mut self, levels: &[u16]) -> Result<()> {
+        if levels.len() != Tlc5955::CHANNELS {
+            return Err(Error::OutOfRange);
+        }
+        let mut packed = [0_u8; Tlc5955::GS_FRAME_LEN];
+        let mut writer = BitWriter::new(&mut packed);
+        writer.push(0, PADDING_BITS);
+        // Select bit LOW for grayscale data
+        writer.push(0, 1);
+        pack_grayscale::<Tlc5955>(&mut writer, levels)?;
+
+        events::frame("grayscale", &packed);
+        events::check(
+            "grayscale write",
+            self.connector
+                .shift(&packed)
+                .and_then(|()| self.connector.latch()),
+        )
+    }
+
+    /// Store a dot correction value. Call `set_dot_correction` to push
+    /// the stored values to the chip.
+    pub fn set_dot_correction_level(
+        &mut self,
+        output: u8,
+        value: u8,
+    ) -> Result<()> {
+        // Dot correction is 7 bits wide
+        store_dot_correction::<Tlc5955>(
+            &mut self.dot_correction,
+            output,
+            value,
+        )?;
+        self.dc_pushed = false;
+        Ok(())
+    }
+
+    /// Get the stored dot correction value for a channel
+    pub fn dot_correction(&self, output: u8) -> Result<u8> {
+        self.dot_correction
+            .get(output as usize)
+            .copied()
+            .ok_or(Error::OutOfRange)
+    }
+
+    /// Get the stored dot correction values for all channels
+    pub fn dot_correction_all(&self) -> &[u8; Tlc5955::CHANNELS] {
+        &self.dot_correction
+    }
+
+    /// Whether the stored control data has been pushed to the chip since
+    /// it was last changed
+    pub fn dot_correction_pushed(&self) -> bool {
+        self.dc_pushed
+    }
+
+    /// Store the maximum current setting of the red, green and blue
+    /// output groups. Only the lower 3 bits of each value are used.
+    pub fn set_max_current(&mut self, red: u8, green: u8, blue: u8) {
+        self.max_current = [red & 0x07, green & 0x07, blue & 0x07];
+        self.dc_pushed = false;
+    }
+
+    /// Store the global brightness of the red, green and blue output
+    /// groups. Only the lower 7 bits of each value are used.
+    pub fn set_brightness(&mut self, red: u8, green: u8, blue: u8) {
+        self.brightness = [red & 0x7f, green & 0x7f, blue & 0x7f];
+        self.dc_pushed = false;
+    }
+
+    /// Store the function control bits
+    pub fn set_function_control(&mut self, function_control: FunctionControl) {
+        self.function_control = function_control;
+        self.dc_pushed = false;
+    }
+
+    /// Get the stored function control bits
+    pub fn function_control(&self) -> FunctionControl {
+        self.function_control
+    }
+
+    ///
+    /// Push the stored control data to the chip. As well as the dot
+    /// correction values this includes the max current, global
+    /// brightness and function control settings.
+    ///
+    pub fn set_dot_correction(&mut self) -> Result<()> {
+        let values = self.dot_correction;
+        self.write_dc_raw(&values)?;
+        self.dc_pushed = true;
+        Ok(())
+    }
+
+    ///
+    /// Packs and latches a full set of dot correction values, bypassing
+    /// the values stored in the driver. The stored max current, global
+    /// brightness and function control settings are still sent.
+    ///
+    /// # Errors
+    ///
+    /// * `Error::OutOfRange` if `values` does not hold exactly 48 values
+    ///
+    pub fn write_dc_raw(&mut self, values: &[u8]) -> Result<()> {
+        if values.len() != Tlc5955::CHANNELS {
+            return Err(Error::OutOfRange);
+        }
+
+        let mut packed = [0_u8; Tlc5955::DC_FRAME_LEN];
+        let mut writer = BitWriter::new(&mut packed);
+        let fc = self.function_control;
+        writer.push(0, PADDING_BITS);
+        // Select bit HIGH for control data
+        writer.push(1, 1);
+        writer.push(CONTROL_COMMAND, 8);
+        writer.push(0, CONTROL_UNUSED_BITS);
+        for bit in &[fc.lsdvlt, fc.espwm, fc.rfresh, fc.tmgrst, fc.dsprpt] {
+            writer.push(*bit as u32, 1);
+        }
+        // Brightness and max current go blue, green, red
+        for value in self.brightness.iter().rev() {
+            writer.push(*value as u32, 7);
+        }
+        for value in self.max_current.iter().rev() {
+            writer.push(*value as u32, 3);
+        }
+        pack_dot_correction::<Tlc5955>(&mut writer, values)?;
+
+        self.dc_pushed = false;
+        events::frame("control", &packed);
+        events::check(
+            "control write",
+            self.connector
+                .shift(&packed)
+                .and_then(|()| self.connector.latch()),
+        )
+    }
+
+    /// Construct a new TLC5955 driver instance from any `Connector`
+    pub fn from_connector(connector: CONNECTOR) -> Self {
+        TLC5955::new(connector)
+    }
+
+    // internal constructor, users should call ::from_pins or ::from_spi
+    fn new(connector: CONNECTOR) -> Self {
+        Self {
+            connector,
+            dot_correction: [0x7f; Tlc5955::CHANNELS],
+            dc_pushed: false,
+            max_current: [0; 3],
+            brightness: [0x7f; 3],
+            function_control: FunctionControl::default(),
+            levels: Levels::new(),
+        }
+    }
+}
+
+impl<DATA, XLAT, SCK> TLC5955<PinConnector<DATA, XLAT, SCK>>
+where
+    DATA: OutputPin,
+    XLAT: OutputPin,
+    SCK: OutputPin,
+{
+    ///
+    /// Construct a new TLC5955 driver instance from DATA, LAT and SCK
+    /// pins.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - the SIN PIN set to output mode
+    /// * `lat` - the LAT PIN set to output mode
+    /// * `sck` - the SCLK PIN set to output mode
+    ///
+    pub fn from_pins(data: DATA, lat: XLAT, sck: SCK) -> Self {
+        TLC5955::new(PinConnector::new(data, lat, sck))
+    }
+}
+
+impl<SPI> TLC5955<SpiConnector<SPI>>
+where
+    SPI: Write<u8>,
+{
+    ///
+    /// Construct a new TLC5955 driver instance from pre-existing SPI in
+    /// full hardware mode, with the hardware CS line wired to LAT.
+    ///
+    /// # Arguments
+    ///
+    /// * `spi` - the SPI interface initialized with MOSI, MISO(unused) and CLK
+    ///
+    pub fn from_spi(spi: SPI) -> Self {
+        TLC5955::new(SpiConnector::new(1, spi))
+    }
+}
+
+impl<SPI, XLAT> TLC5955<SpiConnectorSW<SPI, XLAT>>
+where
+    SPI: Write<u8>,
+    XLAT: OutputPin,
+{
+    ///
+    /// Construct a new TLC5955 driver instance from pre-existing SPI and
+    /// a GPIO driving LAT.
+    ///
+    /// # Arguments
+    ///
+    /// * `spi` - the SPI interface initialized with MOSI, MISO(unused) and CLK
+    /// * `lat` - the LAT PIN set to output mode
+    ///
+    pub fn from_spi_cs(spi: SPI, lat: XLAT) -> Self {
+        TLC5955::new(SpiConnectorSW::new(1, spi, lat))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::*;
+
+    #[test]
+    fn raw_length() {
+        let mut tlc = TLC5955::new(MockConnector::default());
+        assert!(matches!(
+            tlc.write_grayscale_raw(&[0xfff; 96]),
+            Err(Error::OutOfRange)
+        ));
+        assert!(matches!(
+            tlc.write_grayscale_raw(&[0xfff; 47]),
+            Err(Error::OutOfRange)
+        ));
+        assert_eq!(tlc.connector.len, 0);
+    }
+
+    #[test]
+    fn grayscale_packing() {
+        let mut tlc = TLC5955::new(MockConnector::default());
+        tlc.set_level(47, 0xffff).unwrap();
+        tlc.set_level(0, 0x1234).unwrap();
+        assert!(matches!(tlc.set_level(48, 0), Err(Error::OutOfRange)));
+        tlc.update().unwrap();
+
+        let conn = &tlc.connector;
+        assert_eq!(conn.len, 97);
+        // 7 padding bits and the select bit take up the first byte
+        assert_eq!(&conn.last[..3], &[0x00, 0xff, 0xff]);
+        assert_eq!(&conn.last[95..97], &[0x12, 0x34]);
+    }
+
+    #[test]
+    fn control_packing() {
+        let mut tlc = TLC5955::new(MockConnector::default());
+        tlc.set_dot_correction_level(0, 0x55).unwrap();
+        tlc.set_max_current(1, 2, 4);
+        tlc.set_brightness(0, 0, 0x7f);
+        tlc.set_function_control(FunctionControl {
+            lsdvlt: true,
+            ..FunctionControl::default()
+        });
+        tlc.set_dot_correction().unwrap();
+        assert!(tlc.dot_correction_pushed());
+
+        let conn = &tlc.connector;
+        assert_eq!(conn.len, 97);
+        assert_eq!(&conn.last[..2], &[0x01, 0x96]);
+        // Bytes 2..50 hold the unused bits, which run up to 3 bits into
+        // byte 50. LSDVLT comes next.
+        assert_eq!(conn.last[50], 0x04);
+        // TMGRST, DSPRPT, then blue brightness
+        assert_eq!(&conn.last[51..53], &[0x7f, 0x80]);
+        // Red brightness, then max current 100 010 001
+        assert_eq!(&conn.last[53..55], &[0x01, 0x11]);
+        // Channel 0 is last, after the last bit of channel 1
+        assert_eq!(conn.last[96], 0xd5);
+    }
+}
@@ -0,0 +1,280 @@
+use core::fmt;
+
+use embedded_hal::blocking::spi::Write;
+
+use crate::connectors::*;
+use crate::debug::LevelSummary;
+use crate::events;
+use crate::levels::Levels;
+use crate::packing::*;
+use crate::variant::{ChipVariant, Tlc59711};
+use crate::{Error, Result};
+#[cfg(feature = "color")]
+use crate::{Pixel, PixelLayout};
+/// Command word that has to lead every frame for it to be accepted
+const WRITE_COMMAND: u32 = 0x25;
+
+/// Function control bits sent in the header of each frame
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct FunctionControl {
+    /// Outputs change on the rising edge of the grayscale clock if
+    /// true, falling edge if false
+    pub outtmg: bool,
+    /// Use SCKI as the grayscale clock instead of the internal
+    /// oscillator
+    pub extgck: bool,
+    /// Reset the grayscale counter whenever new data is latched
+    pub tmgrst: bool,
+    /// Repeat the PWM cycle automatically
+    pub dsprpt: bool,
+    /// Blank all outputs
+    pub blank: bool,
+}
+
+impl Default for FunctionControl {
+    fn default() -> Self {
+        FunctionControl {
+            outtmg: true,
+            extgck: false,
+            tmgrst: true,
+            dsprpt: true,
+            blank: false,
+        }
+    }
+}
+
+///
+/// Handles communication with the TLC59711 12-channel LED driver. The
+/// TLC59711 has no latch or blanking pins: every frame starts with a
+/// write command and the function control and global brightness bits,
+/// and is latched automatically once the clock stops.
+///
+pub struct TLC59711<CONNECTOR> {
+    connector: CONNECTOR,
+
+    /// Function control bits sent with every frame
+    function_control: FunctionControl,
+    /// Global brightness for the red, green and blue output groups.
+    /// Each value should be in the 0-127 range.
+    brightness: [u8; 3],
+    /// Brightness values for each channel, using the full 16-bit PWM
+    /// range. Channels are ordered R0, G0, B0, R1, ... B3.
+    levels: Levels<Tlc59711, { Tlc59711::CHANNELS }>,
+}
+
+impl<CONNECTOR: fmt::Debug> fmt::Debug for TLC59711<CONNECTOR> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TLC59711")
+            .field("connector", &self.connector)
+            .field("function_control", &self.function_control)
+            .field("brightness", &self.brightness)
+            .field("levels", &LevelSummary(&self.levels.values))
+            .finish()
+    }
+}
+
+impl<CONNECTOR> TLC59711<CONNECTOR>
+where
+    CONNECTOR: Connector,
+{
+    ///
+    /// Blanks the outputs. Unlike the TLC5940 this is done through the
+    /// BLANK function control bit, so a frame is sent immediately.
+    ///
+    pub fn blank(&mut self, is_blank: bool) -> Result<()> {
+        self.function_control.blank = is_blank;
+        self.update()
+    }
+
+    /// Store the function control bits. They are sent on the next
+    /// update.
+    pub fn set_function_control(&mut self, function_control: FunctionControl) {
+        self.function_control = function_control;
+    }
+
+    /// Get the stored function control bits
+    pub fn function_control(&self) -> FunctionControl {
+        self.function_control
+    }
+
+    /// Store the global brightness of the red, green and blue output
+    /// groups. Only the lower 7 bits of each value are used.
+    pub fn set_brightness(&mut self, red: u8, green: u8, blue: u8) {
+        self.brightness = [red & 0x7f, green & 0x7f, blue & 0x7f];
+    }
+
+    /// The connector frames are sent through, see
+    /// `TLC5940::connector_mut`
+    pub fn connector_mut(&mut self) -> &mut CONNECTOR {
+        &mut self.connector
+    }
+
+    /// Store the 16-bit level of one output, see `TLC5940::set_level`
+    pub fn set_level(&mut self, output: u8, level: u16) -> Result<()> {
+        self.levels.set(output, level)
+    }
+
+    /// Store the 16-bit levels of all 12 outputs at once
+    pub fn set_levels(
+        &mut self,
+        levels: [u16; Tlc59711::CHANNELS],
+    ) -> Result<()> {
+        self.levels.set_all(levels);
+        Ok(())
+    }
+
+    /// Lay pixels out as described for `TLC5940::set_pixel_layout`
+    #[cfg(feature = "color")]
+    pub fn set_pixel_layout(&mut self, layout: PixelLayout) {
+        self.levels.set_layout(layout);
+    }
+
+    ///
+    /// Store levels from a sequence of pixels, see `TLC5940::set_pixels`.
+    /// Pixel levels are scaled up from 12 to 16 bits, except for raw
+    /// `u16` pixels which are stored as they are.
+    ///
+    #[cfg(feature = "color")]
+    pub fn set_pixels<P, I>(&mut self, pixels: I) -> Result<()>
+    where
+        P: Pixel,
+        I: IntoIterator<Item = P>,
+    {
+        self.levels.set_pixels(pixels)
+    }
+
+    /// Store levels from a sequence of pixels and send them with the function control and
+    /// brightness header
+    #[cfg(feature = "color")]
+    pub fn write_pixels<P, I>(&mut self, pixels: I) -> Result<()>
+    where
+        P: Pixel,
+        I: IntoIterator<Item = P>,
+    {
+        self.set_pixels(pixels)?;
+        self.update()
+    }
+
+    /// Transfer the stored levels, function control and brightness to
+    /// the chip
+    pub fn update(&mut self) -> Result<()> {
+        let levels = self.levels.values;
+        self.write_grayscale_raw(&levels)
+    }
+
+    ///
+    /// Packs and sends a full set of grayscale values, bypassing the
+    /// levels stored in the driver. The stored function control and
+    /// brightness are still sent in the header.
+    ///
+    /// # Errors
+    ///
+    /// * `Error::OutOfRange` if `levels` does not hold exactly 12 values
+    ///
+    pub fn write_grayscale_raw(&mut self, levels: &[u16]) -> Result<()> {
+        if levels.len() != Tlc59711::CHANNELS {
+            return Err(Error::OutOfRange);
+        }
+        let mut packed = [0_u8; Tlc59711::GS_FRAME_LEN];
+        let mut writer = BitWriter::new(&mut packed);
+        let fc = self.function_control;
+        writer.push(WRITE_COMMAND, 6);
+        for bit in &[fc.outtmg, fc.extgck, fc.tmgrst, fc.dsprpt, fc.blank] {
+            writer.push(*bit as u32, 1);
+        }
+        // Brightness goes blue, green, red
+        for value in self.brightness.iter().rev() {
+            writer.push(*value as u32, 7);
+        }
+        pack_grayscale::<Tlc59711>(&mut writer, levels)?;
+
+        // The TLC59711 latches by itself once the clock stops
+        events::frame("grayscale", &packed);
+        events::check("grayscale write", self.connector.shift(&packed))
+    }
+
+    /// Construct a new TLC59711 driver instance from any `Connector`.
+    /// The connector's latch is never used.
+    pub fn from_connector(connector: CONNECTOR) -> Self {
+        TLC59711::new(connector)
+    }
+
+    // internal constructor, users should call ::from_spi
+    fn new(connector: CONNECTOR) -> Self {
+        Self {
+            connector,
+            function_control: FunctionControl::default(),
+            brightness: [0x7f; 3],
+            levels: Levels::new(),
+        }
+    }
+}
+
+impl<SPI> TLC59711<SpiConnector<SPI>>
+where
+    SPI: Write<u8>,
+{
+    ///
+    /// Construct a new TLC59711 driver instance from pre-existing SPI.
+    /// Only MOSI (SDTI) and CLK (SCKI) are used.
+    ///
+    /// # Arguments
+    ///
+    /// * `spi` - the SPI interface initialized with MOSI, MISO(unused) and CLK
+    ///
+    pub fn from_spi(spi: SPI) -> Self {
+        TLC59711::new(SpiConnector::new(1, spi))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::*;
+
+    #[test]
+    fn raw_length() {
+        let mut tlc = TLC59711::new(MockConnector::default());
+        assert!(matches!(
+            tlc.write_grayscale_raw(&[0xfff; 24]),
+            Err(Error::OutOfRange)
+        ));
+        assert!(matches!(
+            tlc.write_grayscale_raw(&[0xfff; 11]),
+            Err(Error::OutOfRange)
+        ));
+        assert_eq!(tlc.connector.len, 0);
+    }
+
+    #[test]
+    fn packing() {
+        let mut tlc = TLC59711::new(MockConnector::default());
+        tlc.set_level(0, 0x1234).unwrap();
+        tlc.set_level(11, 0xabcd).unwrap();
+        assert!(matches!(tlc.set_level(12, 0), Err(Error::OutOfRange)));
+        tlc.set_brightness(0x7f, 0, 0x01);
+        tlc.update().unwrap();
+
+        let conn = &tlc.connector;
+        assert_eq!(conn.len, 28);
+        // 100101 10110 0000001 0000000 1111111
+        assert_eq!(&conn.last[..4], &[0x96, 0xc0, 0x40, 0x7f]);
+        assert_eq!(&conn.last[4..6], &[0xab, 0xcd]);
+        assert_eq!(&conn.last[26..28], &[0x12, 0x34]);
+    }
+
+    #[test]
+    #[cfg(feature = "color")]
+    fn native_pixels() {
+        let mut tlc = TLC59711::new(MockConnector::default());
+        tlc.set_pixels([0xffffu16, 0x1234].iter().copied()).unwrap();
+        assert_eq!(&tlc.levels.values[..2], &[0xffff, 0x1234]);
+
+        // Other pixels are still scaled up from 12 bits
+        tlc.set_pixels([crate::pixel::Gray8(0x80)].iter().copied())
+            .unwrap();
+        assert_eq!(tlc.levels.values[0], 0x8088);
+    }
+}
@@ -6,6 +6,7 @@ use embedded_hal::digital::v2::{InputPin, OutputPin};
 ///
 /// Any attempt to read or write this pin state returns an
 /// Error::NotConnected
+#[derive(Clone, Copy, Debug, Default)]
 pub struct Unconnected;
 
 impl InputPin for Unconnected {
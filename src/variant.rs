@@ -0,0 +1,119 @@
+/// How a chip tells grayscale data apart from dot correction or control
+/// data
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ModeSwitch {
+    /// The chip only accepts grayscale data
+    None,
+    /// A VPRG pin selects which register the next latch writes to
+    VprgPin,
+    /// A select bit at the start of each frame picks the register
+    SelectBit,
+}
+
+///
+/// Describes a TI shift-register LED driver: how many channels it has,
+/// how wide its grayscale and dot correction values are and how it
+/// switches between them. The packing and level handling shared by the
+/// drivers in this crate is written against this trait.
+///
+/// `TLC594x` drives any chip whose grayscale frame is nothing but its
+/// levels, and which selects dot correction with a VPRG pin if it has
+/// any, such as the TLC5940, TLC5941 and TLC5947. Chips whose frames
+/// carry a header or a select bit, like the TLC59711 and TLC5955, need a
+/// driver of their own built on the same packing code.
+///
+pub trait ChipVariant {
+    /// Number of output channels on a single chip
+    const CHANNELS: usize;
+    /// Width of each grayscale value in bits
+    const GS_BITS: u32;
+    /// Width of each dot correction value in bits, or 0 if the chip has
+    /// no dot correction
+    const DC_BITS: u32;
+    /// Length of a grayscale frame in bytes, including any header and
+    /// padding
+    const GS_FRAME_LEN: usize;
+    /// Length of a dot correction or control data frame in bytes, or 0
+    /// if the chip has no dot correction
+    const DC_FRAME_LEN: usize;
+    /// Whether the chip can load dot correction from on-chip EEPROM. If
+    /// not, the dot correction register has to be written after every
+    /// power up before the outputs behave as expected.
+    const HAS_EEPROM: bool;
+    /// How the chip switches between grayscale and dot correction data
+    const MODE_SWITCH: ModeSwitch;
+}
+
+/// The TLC5940, with dot correction EEPROM
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Tlc5940;
+
+impl ChipVariant for Tlc5940 {
+    const CHANNELS: usize = 16;
+    const GS_BITS: u32 = 12;
+    const DC_BITS: u32 = 6;
+    const GS_FRAME_LEN: usize = 24;
+    const DC_FRAME_LEN: usize = 12;
+    const HAS_EEPROM: bool = true;
+    const MODE_SWITCH: ModeSwitch = ModeSwitch::VprgPin;
+}
+
+/// The TLC5941, which has no EEPROM and so relies on the dot
+/// correction register being written at start up
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Tlc5941;
+
+impl ChipVariant for Tlc5941 {
+    const CHANNELS: usize = 16;
+    const GS_BITS: u32 = 12;
+    const DC_BITS: u32 = 6;
+    const GS_FRAME_LEN: usize = 24;
+    const DC_FRAME_LEN: usize = 12;
+    const HAS_EEPROM: bool = false;
+    const MODE_SWITCH: ModeSwitch = ModeSwitch::VprgPin;
+}
+
+/// The TLC5947, with 24 channels and no dot correction
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Tlc5947;
+
+impl ChipVariant for Tlc5947 {
+    const CHANNELS: usize = 24;
+    const GS_BITS: u32 = 12;
+    const DC_BITS: u32 = 0;
+    const GS_FRAME_LEN: usize = 36;
+    const DC_FRAME_LEN: usize = 0;
+    const HAS_EEPROM: bool = false;
+    const MODE_SWITCH: ModeSwitch = ModeSwitch::None;
+}
+
+/// The TLC59711, with 12 channels and a 32 bit header on every frame
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Tlc59711;
+
+impl ChipVariant for Tlc59711 {
+    const CHANNELS: usize = 12;
+    const GS_BITS: u32 = 16;
+    const DC_BITS: u32 = 0;
+    const GS_FRAME_LEN: usize = 28;
+    const DC_FRAME_LEN: usize = 0;
+    const HAS_EEPROM: bool = false;
+    const MODE_SWITCH: ModeSwitch = ModeSwitch::None;
+}
+
+/// The TLC5955, with 48 channels and a 769 bit shift register. Frames
+/// are led by 7 padding bits to make them a whole number of bytes.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Tlc5955;
+
+impl ChipVariant for Tlc5955 {
+    const CHANNELS: usize = 48;
+    const GS_BITS: u32 = 16;
+    const DC_BITS: u32 = 7;
+    const GS_FRAME_LEN: usize = 97;
+    const DC_FRAME_LEN: usize = 97;
+    const HAS_EEPROM: bool = false;
+    const MODE_SWITCH: ModeSwitch = ModeSwitch::SelectBit;
+}
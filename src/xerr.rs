@@ -0,0 +1,111 @@
+/// What caused XERR to be asserted
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ErrorCause {
+    /// LED Open Detection: an output is on but no current is flowing,
+    /// usually a dead or disconnected LED
+    LedOpen,
+    /// Thermal Error Flag: the chip is overheating
+    Thermal,
+}
+
+///
+/// Filters the XERR line so that chatter near the thermal threshold or
+/// from a weak pullup isn't reported as an error. XERR is read
+/// `samples` times on every call to `tick`, and each tick only counts
+/// as asserted if every read was. The error is reported once XERR has
+/// been asserted for `min_ticks` ticks in a row, and cleared by a tick
+/// on which every read was clear.
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct XerrFilter {
+    samples: u8,
+    min_ticks: u32,
+    /// Consecutive asserted ticks so far
+    asserted_ticks: u32,
+    /// The filtered state
+    active: bool,
+}
+
+impl XerrFilter {
+    ///
+    /// # Arguments
+    ///
+    /// * `samples` - reads of XERR per tick that must all be asserted,
+    ///   at least 1
+    /// * `min_ticks` - consecutive asserted ticks before the error is
+    ///   reported, at least 1
+    ///
+    pub fn new(samples: u8, min_ticks: u32) -> Self {
+        XerrFilter {
+            samples: samples.max(1),
+            min_ticks: min_ticks.max(1),
+            asserted_ticks: 0,
+            active: false,
+        }
+    }
+
+    /// Reads of XERR per tick
+    pub fn samples(&self) -> u8 {
+        self.samples
+    }
+
+    /// Whether the filtered error is currently reported
+    pub fn active(&self) -> bool {
+        self.active
+    }
+
+    /// Feeds in one tick's worth of reads, `asserted` being how many of
+    /// them saw XERR low. Returns the filtered state.
+    pub(crate) fn update(&mut self, asserted: u8) -> bool {
+        if asserted >= self.samples {
+            self.asserted_ticks = self.asserted_ticks.saturating_add(1);
+            if self.asserted_ticks >= self.min_ticks {
+                self.active = true;
+            }
+        } else {
+            self.asserted_ticks = 0;
+            if asserted == 0 {
+                self.active = false;
+            }
+        }
+        self.active
+    }
+}
+
+impl Default for XerrFilter {
+    /// No filtering: every asserted read is reported straight away
+    fn default() -> Self {
+        XerrFilter::new(1, 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unfiltered() {
+        let mut filter = XerrFilter::default();
+        assert!(filter.update(1));
+        assert!(!filter.update(0));
+    }
+
+    #[test]
+    fn chatter_ignored() {
+        let mut filter = XerrFilter::new(4, 3);
+        // A tick with mixed reads neither sets nor clears the error
+        assert!(!filter.update(2));
+        assert!(!filter.update(4));
+        assert!(!filter.update(4));
+        assert!(!filter.update(3));
+        assert!(!filter.update(4));
+        assert!(!filter.update(4));
+        assert!(filter.update(4));
+        assert!(filter.update(3));
+        assert!(!filter.update(0));
+    }
+}
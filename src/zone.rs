@@ -0,0 +1,170 @@
+#[cfg(feature = "diag")]
+use core::fmt;
+
+use crate::{Error, Result};
+
+/// Most zones a driver can hold
+pub const MAX_ZONES: usize = 8;
+
+///
+/// A named set of outputs that are controlled together, e.g. the left
+/// half of a fixture or a cluster of status LEDs. On chained drivers a
+/// zone can take outputs from any chip in the chain.
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Zone {
+    /// Name used to look the zone up with `zone_id`
+    pub name: &'static str,
+    /// The outputs in the zone, counted across the whole chain
+    pub channels: &'static [u8],
+}
+
+/// Handle to a zone added to a driver
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "ufmt", derive(ufmt::derive::uDebug))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ZoneId(usize);
+
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct ZoneEntry {
+    pub(crate) zone: Zone,
+    pub(crate) enabled: bool,
+    /// Highest level sent for any output in the zone
+    pub(crate) ceiling: u16,
+    /// Scale applied to the zone's levels, 255 being full output
+    pub(crate) master: u8,
+}
+
+/// Fixed size table of the zones added to a driver
+#[derive(Debug, Default)]
+pub(crate) struct Zones {
+    entries: [Option<ZoneEntry>; MAX_ZONES],
+}
+
+impl Zones {
+    /// Adds `zone`, failing if the table is full or the name is taken
+    pub(crate) fn add(&mut self, zone: Zone) -> Result<ZoneId> {
+        if self.find(zone.name).is_some() {
+            return Err(Error::OutOfRange);
+        }
+        let idx = self
+            .entries
+            .iter()
+            .position(Option::is_none)
+            .ok_or(Error::OutOfRange)?;
+        self.entries[idx] = Some(ZoneEntry {
+            zone,
+            enabled: true,
+            ceiling: u16::MAX,
+            master: u8::MAX,
+        });
+        Ok(ZoneId(idx))
+    }
+
+    pub(crate) fn find(&self, name: &str) -> Option<ZoneId> {
+        self.entries
+            .iter()
+            .position(|entry| matches!(entry, Some(e) if e.zone.name == name))
+            .map(ZoneId)
+    }
+
+    pub(crate) fn get(&self, id: ZoneId) -> Result<&ZoneEntry> {
+        self.entries
+            .get(id.0)
+            .and_then(Option::as_ref)
+            .ok_or(Error::OutOfRange)
+    }
+
+    pub(crate) fn get_mut(&mut self, id: ZoneId) -> Result<&mut ZoneEntry> {
+        self.entries
+            .get_mut(id.0)
+            .and_then(Option::as_mut)
+            .ok_or(Error::OutOfRange)
+    }
+
+    /// Writes one line per zone for `dump_state`
+    #[cfg(feature = "diag")]
+    pub(crate) fn dump<W: fmt::Write>(&self, w: &mut W) -> fmt::Result {
+        for entry in self.entries.iter().flatten() {
+            if entry.enabled {
+                writeln!(w, "zone {}: enabled", entry.zone.name)?;
+            } else {
+                writeln!(w, "zone {}: disabled", entry.zone.name)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Applies each zone's master scale and ceiling to `levels`, which
+    /// is a copy of the stored levels about to be packed, and turns off
+    /// the outputs of disabled zones
+    pub(crate) fn apply(&self, levels: &mut [u16]) {
+        for (channel, level) in levels.iter_mut().enumerate() {
+            *level = self.level(channel, *level);
+        }
+    }
+
+    /// The level `channel` should be sent at, given its stored level
+    pub(crate) fn level(&self, channel: usize, level: u16) -> u16 {
+        self.entries
+            .iter()
+            .flatten()
+            .filter(|entry| {
+                entry.zone.channels.iter().any(|c| *c as usize == channel)
+            })
+            .fold(level, |level, entry| {
+                if entry.enabled {
+                    let scaled =
+                        level as u32 * entry.master as u32 / u8::MAX as u32;
+                    (scaled as u16).min(entry.ceiling)
+                } else {
+                    0
+                }
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LEFT: Zone = Zone {
+        name: "left",
+        channels: &[0, 1, 2],
+    };
+
+    #[test]
+    fn add_and_find() {
+        let mut zones = Zones::default();
+        let id = zones.add(LEFT).unwrap();
+        assert_eq!(zones.find("left"), Some(id));
+        assert_eq!(zones.find("right"), None);
+        assert!(zones.add(LEFT).is_err());
+    }
+
+    #[test]
+    fn disabled_zones_are_off() {
+        let mut zones = Zones::default();
+        let id = zones.add(LEFT).unwrap();
+        let mut levels = [100; 4];
+        zones.apply(&mut levels);
+        assert_eq!(levels, [100; 4]);
+
+        zones.get_mut(id).unwrap().enabled = false;
+        zones.apply(&mut levels);
+        assert_eq!(levels, [0, 0, 0, 100]);
+    }
+
+    #[test]
+    fn master_and_ceiling() {
+        let mut zones = Zones::default();
+        let id = zones.add(LEFT).unwrap();
+        let entry = zones.get_mut(id).unwrap();
+        entry.master = 0x80;
+        entry.ceiling = 1000;
+        let mut levels = [4095, 1000, 0, 4095];
+        zones.apply(&mut levels);
+        // Scaled to just over half, then capped
+        assert_eq!(levels, [1000, 501, 0, 4095]);
+    }
+}